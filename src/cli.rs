@@ -0,0 +1,318 @@
+use std::time::Duration;
+
+use structopt::StructOpt;
+
+use crate::config::Config;
+use crate::state::App;
+use crate::timer::Countdown;
+
+#[derive(StructOpt)]
+#[structopt(about = "A task-oriented TUI timer. Run without a command to open the TUI.")]
+pub struct Opt {
+    /// Ask what to do with the time after this long without a keypress in the TUI, `0` to
+    /// never ask (default 30m)
+    #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+    pub idle: Option<Duration>,
+    /// Run one timer at a time: starting a task stops the one that was running
+    #[structopt(long)]
+    pub exclusive: bool,
+    /// How long you want to work each day, e.g. `6h`, shown as progress in the header
+    #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+    pub daily_goal: Option<Duration>,
+    /// Suggest a break after a timer has been running this long, e.g. `50m`
+    #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+    pub break_reminder: Option<Duration>,
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Commands take a `task` argument which is either the number shown by `list`
+/// or (part of) the task description.
+#[derive(StructOpt)]
+pub enum Command {
+    /// Add a new running task, e.g. `add Fix login bug +web #bug ~45m`
+    Add {
+        #[structopt(required = true)]
+        description: Vec<String>,
+    },
+    /// Start a task's timer
+    Start { task: String },
+    /// Stop a task's timer, or every running timer if no task is given
+    Stop { task: Option<String> },
+    /// Start a stopped timer or stop a running one
+    Toggle { task: String },
+    /// List active tasks, optionally only those matching a filter such as `+web #bug`
+    List { filter: Vec<String> },
+    /// Stop a task and move it to the completed quests
+    Complete { task: String },
+    /// Delete a task and its tracked time
+    Delete { task: String },
+    /// Change a task's description, e.g. `rename login Fix login redirect ~1h`
+    Rename {
+        task: String,
+        #[structopt(required = true)]
+        description: Vec<String>,
+    },
+    /// Correct a task's time: `+15m` or `-1h30m` adds or removes time, `=2h` sets it
+    #[structopt(setting = structopt::clap::AppSettings::AllowLeadingHyphen)]
+    Adjust {
+        task: String,
+        amount: String,
+    },
+    /// Switch a task between counting up and counting down from its estimate
+    Countdown { task: String },
+    /// Start a break, stopping the running timers, or end the break in progress
+    Break,
+    /// Split a task into subtasks, e.g. `split login write tests ~30m, fix redirect ~1h`
+    Split {
+        task: String,
+        #[structopt(required = true)]
+        subtasks: Vec<String>,
+    },
+    /// Show running timers and the total time
+    Status,
+    /// Print estimation accuracy over all completed quests
+    Report,
+}
+
+impl Opt {
+    /// Let options given on the command line override the config file
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(idle) = self.idle {
+            config.idle = idle;
+        }
+        if self.exclusive {
+            config.exclusive = true;
+        }
+        if let Some(goal) = self.daily_goal {
+            config.daily_goal = goal;
+        }
+        if let Some(every) = self.break_reminder {
+            config.break_reminder = every;
+        }
+    }
+}
+
+pub fn run(command: Command, config: &Config) -> Result<(), crate::Error> {
+    if let Command::Report = command {
+        let tasks = crate::storage::load_completed_tasks()?;
+        print!("{}", crate::report::Report::new(&tasks));
+        return Ok(());
+    }
+
+    let mut app = crate::storage::load_state()?;
+    app.configure(config);
+    let clock = app.clock.clone();
+    if let Some(skew) = app.check_clock() {
+        warn!("the system clock moved back by {}, running timers were adjusted", format_duration(skew));
+        app.save()?;
+    }
+    // with `countdown_end: Pause`, countdowns which ran out since the TUI was last open stop
+    app.end_countdowns(std::time::SystemTime::UNIX_EPOCH)?;
+    match command {
+        Command::Add { description } => {
+            app.add_task(&description.join(" "))?;
+            println!("started {}", describe(app.tasks.len() - 1, &app));
+        }
+        Command::Start { task } => {
+            select(&mut app, &task)?;
+            if !app.tasks[app.selected_task].timer.is_running() {
+                app.toggle_play_pause_selected_task()?;
+            }
+            println!("started {}", describe(app.selected_task, &app));
+        }
+        Command::Stop { task: Some(task) } => {
+            select(&mut app, &task)?;
+            if app.tasks[app.selected_task].timer.is_running() {
+                app.toggle_play_pause_selected_task()?;
+            }
+            println!("stopped {}", describe(app.selected_task, &app));
+        }
+        Command::Stop { task: None } => {
+            for i in app.stop_all()? {
+                println!("stopped {}", describe(i, &app));
+            }
+        }
+        Command::Toggle { task } => {
+            select(&mut app, &task)?;
+            app.toggle_play_pause_selected_task()?;
+            let task = &app.tasks[app.selected_task];
+            let verb = if task.timer.is_running() { "started" } else { "stopped" };
+            println!("{} {}", verb, describe(app.selected_task, &app));
+        }
+        Command::List { filter } => {
+            let filter = filter.join(" ");
+            for (i, task) in app.tasks.iter().enumerate() {
+                if task.matches(&filter) {
+                    println!("{}", describe(i, &app));
+                }
+            }
+        }
+        Command::Complete { task } => {
+            select(&mut app, &task)?;
+            let line = describe(app.selected_task, &app);
+            app.complete_selected_task()?;
+            println!("completed {}", line);
+        }
+        Command::Delete { task } => {
+            select(&mut app, &task)?;
+            let line = describe(app.selected_task, &app);
+            app.delete_selected_task()?;
+            println!("deleted {}", line);
+        }
+        Command::Rename { task, description } => {
+            select(&mut app, &task)?;
+            app.rename_selected_task(&description.join(" "))?;
+            println!("renamed {}", describe(app.selected_task, &app));
+        }
+        Command::Adjust { task, amount } => {
+            select(&mut app, &task)?;
+            app.adjust_selected_task(&amount)?;
+            println!("adjusted {}", describe(app.selected_task, &app));
+        }
+        Command::Countdown { task } => {
+            select(&mut app, &task)?;
+            app.toggle_countdown_selected_task()?;
+            let task = &app.tasks[app.selected_task];
+            let verb = if task.countdown { "counting down" } else { "counting up" };
+            println!("{} {}", verb, describe(app.selected_task, &app));
+        }
+        Command::Break => {
+            app.toggle_break()?;
+            let on_break = app.tasks.iter().any(|task| task.is_break && task.timer.is_running());
+            println!("{}", if on_break { "on a break" } else { "back to work" });
+            for (i, task) in app.tasks.iter().enumerate() {
+                if task.timer.is_running() && !task.is_break {
+                    println!("started {}", describe(i, &app));
+                }
+            }
+        }
+        Command::Split { task, subtasks } => {
+            select(&mut app, &task)?;
+            let index = app.selected_task;
+            app.split_selected_task(&subtasks.join(" "))?;
+            println!("split {}", describe(index, &app));
+            let id = app.tasks[index].id;
+            for i in (0..app.tasks.len()).filter(|&i| app.tasks[i].parent == id) {
+                println!("  {}", describe(i, &app));
+            }
+        }
+        Command::Status => {
+            let mut running = (0..app.tasks.len()).filter(|&i| app.tasks[i].timer.is_running()).peekable();
+            if running.peek().is_none() {
+                println!("no timers running");
+            }
+            for i in running {
+                println!("{}", describe(i, &app));
+            }
+            println!("total time: {}", format_duration(app.active_elapsed()));
+            if let Some(breaks) = app.tasks.iter().find(|task| task.is_break) {
+                println!("breaks: {}", format_duration(breaks.timer.elapsed(clock.as_ref())));
+            }
+            app.refresh_completed_today()?;
+            let today = app.today_elapsed();
+            match app.daily_goal {
+                Some(goal) if !goal.is_zero() => println!(
+                    "today: {} of {} ({:.0}%)",
+                    format_duration(today),
+                    format_duration(goal),
+                    today.as_secs_f64() / goal.as_secs_f64() * 100.0
+                ),
+                _ => println!("today: {}", format_duration(today)),
+            }
+        }
+        Command::Report => unreachable!(),
+    }
+    Ok(())
+}
+
+/// Point `app.selected_task` at the task matching `query`: a 1-based index,
+/// an exact description, or a case-insensitive substring of exactly one description.
+/// A number that isn't an index is looked for in the descriptions, e.g. `2024 planning`.
+fn select(app: &mut App, query: &str) -> Result<(), crate::Error> {
+    if let Ok(number) = query.parse::<usize>() {
+        if number >= 1 && number <= app.tasks.len() {
+            app.selected_task = number - 1;
+            return Ok(());
+        }
+    }
+
+    if let Some(index) = app.tasks.iter().position(|task| task.description == query) {
+        app.selected_task = index;
+        return Ok(());
+    }
+
+    let query = query.to_lowercase();
+    let matches: Vec<usize> = app
+        .tasks
+        .iter()
+        .enumerate()
+        .filter(|(_, task)| task.description.to_lowercase().contains(&query))
+        .map(|(i, _)| i)
+        .collect();
+    match matches.as_slice() {
+        [index] => {
+            app.selected_task = *index;
+            Ok(())
+        }
+        [] if query.parse::<usize>().is_ok() => {
+            Err(format!("no task number {} or task matching it", query).into())
+        }
+        [] => Err(format!("no task matching '{}'", query).into()),
+        _ => Err(format!("'{}' matches {} tasks, be more specific", query, matches.len()).into()),
+    }
+}
+
+/// One line summary of a task, numbered as `select` expects
+fn describe(index: usize, app: &App) -> String {
+    let task = &app.tasks[index];
+    let running_icon = if task.timer.is_running() { "►" } else { " " };
+    let mut line = format!("{:>3} {} {}", index + 1, running_icon, task.description);
+    if let Some(project) = &task.project {
+        line.push_str(&format!(" +{}", project));
+    }
+    for tag in &task.tags {
+        line.push_str(&format!(" #{}", tag));
+    }
+    // a split task shows the total of its parts
+    let (elapsed, estimate) = app.rollup(index);
+    match app.countdown(index) {
+        Some(Countdown::Remaining(left)) => line.push_str(&format!(" - {} left", format_duration(left))),
+        Some(Countdown::Overtime(over)) if over.as_secs() == 0 => line.push_str(" - time's up"),
+        Some(Countdown::Overtime(over)) => line.push_str(&format!(" - -{} over", format_duration(over))),
+        None => line.push_str(&format!(" - {}", format_duration(elapsed))),
+    }
+    if let Some(estimate) = estimate {
+        line.push_str(&format!(" / {}", format_duration(estimate)));
+    }
+    line
+}
+
+fn format_duration(duration: Duration) -> humantime::FormattedDuration {
+    humantime::format_duration(Duration::new(duration.as_secs(), 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::state::Task;
+    use std::rc::Rc;
+
+    #[test]
+    fn numbers_past_the_end_are_looked_for_in_descriptions() {
+        let clock = FakeClock::new();
+        let mut app = App {
+            tasks: vec![Task::new("email", &clock), Task::new("2024 planning", &clock)],
+            clock: Rc::new(clock),
+            ..Default::default()
+        };
+        select(&mut app, "2").unwrap();
+        assert_eq!(app.selected_task, 1);
+        select(&mut app, "1").unwrap();
+        assert_eq!(app.selected_task, 0);
+        select(&mut app, "2024").unwrap();
+        assert_eq!(app.selected_task, 1);
+        assert!(select(&mut app, "3").is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use std::time::SystemTime;
+
+/// Source of the current wall-clock time. Everything that reads the time takes one of these,
+/// so tests can control it with a `FakeClock`.
+pub trait Clock {
+    fn now(&self) -> SystemTime;
+}
+
+/// The real time
+#[derive(Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+#[cfg(test)]
+pub use fake::FakeClock;
+
+#[cfg(test)]
+mod fake {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::time::{Duration, SystemTime};
+
+    /// A clock which only moves when told to. Clones share the same time.
+    #[derive(Clone)]
+    pub struct FakeClock {
+        time: Rc<Cell<SystemTime>>,
+    }
+
+    impl FakeClock {
+        /// A clock reading `2022-03-01 12:00:00 UTC`
+        pub fn new() -> FakeClock {
+            FakeClock {
+                time: Rc::new(Cell::new(
+                    SystemTime::UNIX_EPOCH + Duration::from_secs(1_646_136_000),
+                )),
+            }
+        }
+
+        pub fn advance(&self, duration: Duration) {
+            self.time.set(self.time.get() + duration);
+        }
+
+        /// Move the clock backwards, as an NTP correction or a user changing the time might
+        pub fn rewind(&self, duration: Duration) {
+            self.time.set(self.time.get() - duration);
+        }
+    }
+
+    impl super::Clock for FakeClock {
+        fn now(&self) -> SystemTime {
+            self.time.get()
+        }
+    }
+}
@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Deserializer};
+use tui::style::Color;
+
+/// Settings from `~/.timeknight/config.ron`. Everything is optional, e.g.
+///
+/// ```ron
+/// (
+///     data_dir: "~/Dropbox/timeknight",
+///     idle: "15m",
+///     exclusive: true,
+///     daily_goal: "6h",
+///     countdown_end: Pause,
+///     break_reminder: "50m",
+///     keys: { "toggle": ["l", "space"], "quit": ["q", "ctrl-c"] },
+///     theme: (running: "#00c8ff", header: "black"),
+/// )
+/// ```
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Where timers and completed quests are kept, instead of `~/.timeknight`
+    #[serde(deserialize_with = "path")]
+    pub data_dir: Option<PathBuf>,
+    /// How often the TUI redraws while no key is pressed
+    #[serde(deserialize_with = "duration")]
+    pub poll_rate: Duration,
+    /// Ask what to do with the time after this long without a keypress, `0` to never ask
+    #[serde(deserialize_with = "duration")]
+    pub idle: Duration,
+    /// A timer running longer than this without a break was probably left on by accident
+    #[serde(deserialize_with = "duration")]
+    pub long_run: Duration,
+    /// Run one timer at a time
+    pub exclusive: bool,
+    /// How long to work each day, `0` for no goal
+    #[serde(deserialize_with = "duration")]
+    pub daily_goal: Duration,
+    /// What a task counting down from its estimate does once the time is up
+    pub countdown_end: CountdownEnd,
+    /// Suggest a break after a timer has been running this long, `0` for no reminders
+    #[serde(deserialize_with = "duration")]
+    pub break_reminder: Duration,
+    pub keys: Keymap,
+    pub theme: Theme,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            data_dir: None,
+            poll_rate: Duration::from_millis(500),
+            idle: Duration::from_secs(30 * 60),
+            long_run: Duration::from_secs(8 * 60 * 60),
+            exclusive: false,
+            daily_goal: Duration::ZERO,
+            countdown_end: CountdownEnd::Overtime,
+            break_reminder: Duration::ZERO,
+            keys: Keymap::default(),
+            theme: Theme::default(),
+        }
+    }
+}
+
+/// Load the config file, or the defaults if there isn't one
+pub fn load() -> Result<Config, crate::Error> {
+    let mut path = dirs::home_dir().ok_or(crate::Error::MissingHome)?;
+    path.push(".timeknight");
+    path.push("config.ron");
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+
+    let mut config: Config = ron::from_str(&std::fs::read_to_string(&path)?)
+        .map_err(|e| crate::Error::Parse(format!("{}: {}", path.display(), e)))?;
+    // `~/` in the data directory is the home directory
+    if let Some(dir) = &config.data_dir {
+        if let Ok(rest) = dir.strip_prefix("~") {
+            config.data_dir = dirs::home_dir().map(|home| home.join(rest));
+        }
+    }
+    Ok(config)
+}
+
+fn path<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<PathBuf>, D::Error> {
+    Ok(Some(PathBuf::deserialize(deserializer)?))
+}
+
+fn duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    humantime::parse_duration(&text).map_err(serde::de::Error::custom)
+}
+
+/// What happens when a task counting down from its estimate runs out of time
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+pub enum CountdownEnd {
+    /// Stop the timer at the estimate
+    Pause,
+    /// Keep going, counting the time over the estimate
+    Overtime,
+}
+
+/// Something a key can be bound to on the timers and history screens
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Add,
+    Edit,
+    Up,
+    Down,
+    Toggle,
+    Reset,
+    Delete,
+    Complete,
+    Adjust,
+    Countdown,
+    Break,
+    Split,
+    Fold,
+    Filter,
+    Undo,
+    Redo,
+    Save,
+    Report,
+    History,
+    Reopen,
+    Help,
+}
+
+impl Action {
+    pub const ALL: [Action; 22] = [
+        Action::Add,
+        Action::Edit,
+        Action::Up,
+        Action::Down,
+        Action::Toggle,
+        Action::Reset,
+        Action::Delete,
+        Action::Complete,
+        Action::Adjust,
+        Action::Countdown,
+        Action::Break,
+        Action::Split,
+        Action::Fold,
+        Action::Filter,
+        Action::Undo,
+        Action::Redo,
+        Action::Save,
+        Action::Report,
+        Action::History,
+        Action::Reopen,
+        Action::Help,
+        Action::Quit,
+    ];
+
+    /// Name used for the action in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Add => "add",
+            Action::Edit => "edit",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Toggle => "toggle",
+            Action::Reset => "reset",
+            Action::Delete => "delete",
+            Action::Complete => "complete",
+            Action::Adjust => "adjust",
+            Action::Countdown => "countdown",
+            Action::Break => "break",
+            Action::Split => "split",
+            Action::Fold => "fold",
+            Action::Filter => "filter",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Save => "save",
+            Action::Report => "report",
+            Action::History => "history",
+            Action::Reopen => "reopen",
+            Action::Help => "help",
+        }
+    }
+
+    /// What the action does, for the help popup
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Add => "add timer",
+            Action::Edit => "edit description",
+            Action::Up => "select previous",
+            Action::Down => "select next",
+            Action::Toggle => "start/stop timer",
+            Action::Reset => "reset timer",
+            Action::Delete => "delete timer",
+            Action::Complete => "complete timer",
+            Action::Adjust => "adjust time (+15m, =2h)",
+            Action::Countdown => "count down from estimate",
+            Action::Break => "take/end a break",
+            Action::Split => "split into subtasks",
+            Action::Fold => "fold/unfold subtasks",
+            Action::Filter => "filter / search",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Save => "save",
+            Action::Report => "estimation report",
+            Action::History => "completed quests",
+            Action::Reopen => "reopen quest",
+            Action::Help => "help",
+        }
+    }
+
+    /// Whether the action works on the selected timer
+    pub fn needs_selection(self) -> bool {
+        matches!(
+            self,
+            Action::Edit
+                | Action::Toggle
+                | Action::Reset
+                | Action::Delete
+                | Action::Complete
+                | Action::Adjust
+                | Action::Countdown
+                | Action::Split
+                | Action::Fold
+        )
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q"],
+            Action::Add => &["a"],
+            Action::Edit => &["e"],
+            Action::Up => &["k", "up"],
+            Action::Down => &["j", "down"],
+            Action::Toggle => &["l"],
+            Action::Reset => &["r"],
+            Action::Delete => &["x"],
+            Action::Complete => &["C"],
+            Action::Adjust => &["t"],
+            Action::Countdown => &["c"],
+            Action::Break => &["b"],
+            Action::Split => &["S"],
+            Action::Fold => &["z"],
+            Action::Filter => &["/"],
+            Action::Undo => &["u"],
+            Action::Redo => &["ctrl-r"],
+            Action::Save => &["s"],
+            Action::Report => &["R"],
+            Action::History => &["tab"],
+            Action::Reopen => &["o"],
+            Action::Help => &["?"],
+        }
+    }
+}
+
+/// A key with the ctrl/alt modifiers it needs. Shift is left out, `C` already implies it.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    /// Parse a key such as `q`, `C`, `space`, `tab`, `f1` or `ctrl-r`
+    pub fn parse(text: &str) -> Result<Key, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        loop {
+            if let Some(after) = rest.strip_prefix("ctrl-") {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("alt-") {
+                modifiers |= KeyModifiers::ALT;
+                rest = after;
+            } else {
+                break;
+            }
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "tab" => KeyCode::Tab,
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                name => match name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n) => KeyCode::F(n),
+                    None => return Err(format!("unknown key '{}'", text)),
+                },
+            },
+        };
+        Ok(Key { code, modifiers })
+    }
+
+    fn matches(&self, event: &KeyEvent) -> bool {
+        event.code == self.code
+            && event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT) == self.modifiers
+    }
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "^")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "M-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// Which keys trigger which actions
+pub struct Keymap {
+    bindings: Vec<(Action, Vec<Key>)>,
+}
+
+impl Keymap {
+    /// The action bound to a key press, if any
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.iter().any(|key| key.matches(event)))
+            .map(|(action, _)| *action)
+    }
+
+    /// Keys bound to an action, in the order they were given
+    pub fn keys(&self, action: Action) -> &[Key] {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == action)
+            .map_or(&[], |(_, keys)| keys.as_slice())
+    }
+
+    /// The first key bound to an action, as shown in the help, e.g. `^r`
+    pub fn label(&self, action: Action) -> String {
+        self.keys(action)
+            .first()
+            .map_or_else(|| "-".to_string(), |key| key.to_string())
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap {
+            bindings: Action::ALL
+                .iter()
+                .map(|action| {
+                    let keys = action
+                        .default_keys()
+                        .iter()
+                        .map(|key| Key::parse(key).expect("default keys parse"))
+                        .collect();
+                    (*action, keys)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Keys in the config file replace the default keys of the actions they name
+impl<'de> Deserialize<'de> for Keymap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Keymap, D::Error> {
+        let overrides = HashMap::<String, Vec<String>>::deserialize(deserializer)?;
+        let mut keymap = Keymap::default();
+        for (name, keys) in overrides {
+            let action = Action::ALL
+                .iter()
+                .find(|action| action.name() == name)
+                .ok_or_else(|| serde::de::Error::custom(format!("unknown action '{}'", name)))?;
+            let keys = keys
+                .iter()
+                .map(|key| Key::parse(key))
+                .collect::<Result<Vec<Key>, String>>()
+                .map_err(serde::de::Error::custom)?;
+            for (bound, bound_keys) in keymap.bindings.iter_mut() {
+                if bound == action {
+                    *bound_keys = keys.clone();
+                }
+            }
+        }
+        // a key can only do one thing, so a config binding a key to two actions is rejected,
+        // including when one of them only has it by default
+        for (i, (action, keys)) in keymap.bindings.iter().enumerate() {
+            for key in keys {
+                let taken = keymap.bindings[..i].iter().find(|(_, other_keys)| other_keys.contains(key));
+                if let Some((other, _)) = taken {
+                    return Err(serde::de::Error::custom(format!(
+                        "key '{}' is bound to both '{}' and '{}'",
+                        key,
+                        other.name(),
+                        action.name()
+                    )));
+                }
+            }
+        }
+        Ok(keymap)
+    }
+}
+
+/// Colours of the TUI, given as names (`blue`, `lightyellow`) or `#rrggbb`
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    /// Running timers
+    #[serde(deserialize_with = "color")]
+    pub running: Color,
+    /// Timers past their estimate
+    #[serde(deserialize_with = "color")]
+    pub overrun: Color,
+    /// Estimate gauges within the estimate, and a reached daily goal
+    #[serde(deserialize_with = "color")]
+    pub on_track: Color,
+    /// Background of the header and popups
+    #[serde(deserialize_with = "color")]
+    pub header: Color,
+    /// Background of the title
+    #[serde(deserialize_with = "color")]
+    pub title: Color,
+    /// Background of the time worked today and the active input box title
+    #[serde(deserialize_with = "color")]
+    pub accent: Color,
+    /// The break timer and the break reminder
+    #[serde(deserialize_with = "color")]
+    pub on_break: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            running: Color::Rgb(255, 0, 200),
+            overrun: Color::Rgb(255, 80, 0),
+            on_track: Color::Green,
+            header: Color::Rgb(20, 20, 20),
+            title: Color::Blue,
+            accent: Color::LightYellow,
+            on_break: Color::Cyan,
+        }
+    }
+}
+
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    parse_color(&text).ok_or_else(|| serde::de::Error::custom(format!("unknown colour '{}'", text)))
+}
+
+fn parse_color(text: &str) -> Option<Color> {
+    if let Some(hex) = text.strip_prefix('#') {
+        let value = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6)?;
+        return Some(Color::Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8));
+    }
+    Some(match text.to_lowercase().as_str() {
+        "reset" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" => Color::Gray,
+        "darkgray" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_partial_config_over_the_defaults() {
+        let config: Config = ron::from_str(
+            r##"(
+                data_dir: "/srv/timers",
+                idle: "15m",
+                keys: { "toggle": ["space", "ctrl-t"] },
+                theme: (running: "#00c8ff"),
+            )"##,
+        )
+        .unwrap();
+        assert_eq!(config.data_dir, Some(PathBuf::from("/srv/timers")));
+        assert_eq!(config.idle, Duration::from_secs(15 * 60));
+        assert_eq!(config.poll_rate, Duration::from_millis(500));
+        assert!(config.theme.running == Color::Rgb(0, 200, 255));
+
+        let press = |code, modifiers| config.keys.action(&KeyEvent::new(code, modifiers));
+        assert!(press(KeyCode::Char(' '), KeyModifiers::NONE) == Some(Action::Toggle));
+        assert!(press(KeyCode::Char('t'), KeyModifiers::CONTROL) == Some(Action::Toggle));
+        assert!(press(KeyCode::Char('l'), KeyModifiers::NONE).is_none());
+        assert!(press(KeyCode::Char('C'), KeyModifiers::SHIFT) == Some(Action::Complete));
+        assert_eq!(config.keys.label(Action::Redo), "^r");
+    }
+
+    #[test]
+    fn rejects_unknown_actions_and_keys() {
+        assert!(ron::from_str::<Config>(r#"(keys: { "fly": ["f"] })"#).is_err());
+        assert!(ron::from_str::<Config>(r#"(keys: { "quit": ["hyper-q"] })"#).is_err());
+    }
+
+    #[test]
+    fn rejects_a_key_bound_to_two_actions() {
+        let error = ron::from_str::<Config>(r#"(keys: { "quit": ["x"] })"#).err().unwrap().to_string();
+        assert!(error.contains("'quit'") && error.contains("'delete'"), "{}", error);
+        // unless the other action is moved off it
+        assert!(ron::from_str::<Config>(r#"(keys: { "quit": ["x"], "delete": ["d"] })"#).is_ok());
+    }
+}
@@ -0,0 +1,266 @@
+use unicode_width::UnicodeWidthStr;
+
+/// Single line text input with a cursor, word deletion and recall of earlier entries.
+///
+/// The cursor is kept as a char index so that multi-byte characters are stepped over whole.
+#[derive(Default)]
+pub struct LineEditor {
+    text: String,
+    cursor: usize,
+    /// Earlier entries which can be recalled with up/down, oldest first
+    history: Vec<String>,
+    /// Index into `history` of the entry being shown, if one has been recalled
+    recalled: Option<usize>,
+    /// What was typed before recalling, used as the prefix entries must match
+    draft: String,
+}
+
+impl LineEditor {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Display width of the text in front of the cursor, where the terminal cursor goes
+    pub fn cursor_width(&self) -> usize {
+        self.text[..self.cursor_byte()].width()
+    }
+
+    /// Replace the text, putting the cursor at the end
+    pub fn set(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = self.text.chars().count();
+    }
+
+    /// Entries to offer for recall and completion, oldest first
+    pub fn set_history(&mut self, history: Vec<String>) {
+        self.history = history;
+        self.recalled = None;
+    }
+
+    /// Empty the editor and forget its history, returning what was typed
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        self.history.clear();
+        self.recalled = None;
+        std::mem::take(&mut self.text)
+    }
+
+    /// Type a character at the cursor
+    pub fn insert(&mut self, c: char) {
+        let at = self.cursor_byte();
+        self.text.insert(at, c);
+        self.cursor += 1;
+    }
+
+    /// Delete the character before the cursor
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            let at = self.cursor_byte();
+            self.text.remove(at);
+        }
+    }
+
+    /// Delete the character under the cursor
+    pub fn delete(&mut self) {
+        if self.cursor < self.len() {
+            let at = self.cursor_byte();
+            self.text.remove(at);
+        }
+    }
+
+    /// Delete back to the start of the word before the cursor (Ctrl-W)
+    pub fn delete_word(&mut self) {
+        let end = self.cursor_byte();
+        let before = self.text[..end].trim_end();
+        let start = before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        self.cursor -= self.text[start..end].chars().count();
+        self.text.replace_range(start..end, "");
+    }
+
+    /// Delete everything before the cursor (Ctrl-U)
+    pub fn delete_to_start(&mut self) {
+        let end = self.cursor_byte();
+        self.text.replace_range(..end, "");
+        self.cursor = 0;
+    }
+
+    /// Delete everything from the cursor on (Ctrl-K)
+    pub fn delete_to_end(&mut self) {
+        let start = self.cursor_byte();
+        self.text.truncate(start);
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.len());
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.len();
+    }
+
+    /// Show the next older history entry starting with what was typed
+    pub fn previous(&mut self) {
+        if self.recalled.is_none() {
+            self.draft = self.text.clone();
+        }
+        let before = self.recalled.unwrap_or(self.history.len());
+        let found = self.history[..before]
+            .iter()
+            .rposition(|entry| entry.starts_with(&self.draft) && *entry != self.text);
+        if let Some(index) = found {
+            self.recalled = Some(index);
+            let entry = self.history[index].clone();
+            self.set(&entry);
+        }
+    }
+
+    /// Show the next newer history entry starting with what was typed, or go back to what
+    /// was typed after the newest one
+    pub fn next(&mut self) {
+        let after = match self.recalled {
+            Some(index) => index + 1,
+            None => return,
+        };
+        let found = self.history[after..]
+            .iter()
+            .position(|entry| entry.starts_with(&self.draft) && *entry != self.text);
+        match found {
+            Some(offset) => {
+                self.recalled = Some(after + offset);
+                let entry = self.history[after + offset].clone();
+                self.set(&entry);
+            }
+            None => {
+                self.recalled = None;
+                let draft = std::mem::take(&mut self.draft);
+                self.set(&draft);
+            }
+        }
+    }
+
+    /// The rest of the newest history entry which starts with the text, offered while the
+    /// cursor is at the end
+    pub fn completion(&self) -> Option<&str> {
+        if self.text.is_empty() || self.cursor < self.len() {
+            return None;
+        }
+        self.history
+            .iter()
+            .rev()
+            .find(|entry| entry.len() > self.text.len() && entry.starts_with(&self.text))
+            .map(|entry| &entry[self.text.len()..])
+    }
+
+    /// Accept the offered completion, if any
+    pub fn complete(&mut self) {
+        if let Some(rest) = self.completion().map(str::to_string) {
+            self.text.push_str(&rest);
+            self.cursor = self.len();
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    /// Byte offset of the cursor in `text`
+    fn cursor_byte(&self) -> usize {
+        self.text
+            .char_indices()
+            .nth(self.cursor)
+            .map_or(self.text.len(), |(i, _)| i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor(text: &str) -> LineEditor {
+        let mut editor = LineEditor::default();
+        editor.set(text);
+        editor
+    }
+
+    #[test]
+    fn edits_at_the_cursor_across_wide_characters() {
+        let mut editor = editor("日本 docs");
+        editor.home();
+        editor.right();
+        editor.right();
+        assert_eq!(editor.cursor_width(), 4);
+        editor.insert('語');
+        editor.left();
+        editor.backspace();
+        assert_eq!(editor.text(), "日語 docs");
+        editor.delete();
+        assert_eq!(editor.text(), "日 docs");
+    }
+
+    #[test]
+    fn deletes_words_and_line_ends() {
+        let mut editor = editor("fix the  login bug");
+        editor.delete_word();
+        assert_eq!(editor.text(), "fix the  login ");
+        editor.delete_word();
+        assert_eq!(editor.text(), "fix the  ");
+        editor.left();
+        editor.left();
+        editor.delete_to_end();
+        assert_eq!(editor.text(), "fix the");
+        editor.left();
+        editor.delete_to_start();
+        assert_eq!(editor.text(), "e");
+    }
+
+    #[test]
+    fn deletes_words_separated_by_wide_whitespace() {
+        let mut editor = editor("a\u{3000}b\u{a0}c");
+        editor.delete_word();
+        assert_eq!(editor.text(), "a\u{3000}b\u{a0}");
+        editor.delete_word();
+        assert_eq!(editor.text(), "a\u{3000}");
+        assert_eq!(editor.cursor_width(), 3);
+    }
+
+    #[test]
+    fn recalls_history_matching_what_was_typed() {
+        let mut editor = editor("re");
+        editor.set_history(vec!["review PR".into(), "write docs".into(), "release".into()]);
+        editor.previous();
+        assert_eq!(editor.text(), "release");
+        editor.previous();
+        assert_eq!(editor.text(), "review PR");
+        editor.previous();
+        assert_eq!(editor.text(), "review PR");
+        editor.next();
+        assert_eq!(editor.text(), "release");
+        editor.next();
+        assert_eq!(editor.text(), "re");
+    }
+
+    #[test]
+    fn completes_from_the_newest_matching_entry() {
+        let mut editor = editor("wr");
+        editor.set_history(vec!["write tests".into(), "write docs".into()]);
+        assert_eq!(editor.completion(), Some("ite docs"));
+        editor.left();
+        assert_eq!(editor.completion(), None);
+        editor.end();
+        editor.complete();
+        assert_eq!(editor.text(), "write docs");
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+
+/// Everything that can go wrong, so callers can tell a failed write from bad input
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed
+    Io(std::io::Error),
+    /// A data file, config file or typed duration could not be understood
+    Parse(String),
+    /// There is no home directory to keep data and config in
+    MissingHome,
+    /// Another timeknight holds the lock, so nothing can be saved
+    ReadOnly,
+    /// A request that doesn't make sense, e.g. an empty description or unknown task
+    Invalid(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Parse(message) => write!(f, "could not parse {}", message),
+            Error::MissingHome => write!(f, "could not find $HOME directory"),
+            Error::ReadOnly => write!(f, "another timeknight is running, this one is read-only"),
+            Error::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<ron::Error> for Error {
+    fn from(e: ron::Error) -> Error {
+        Error::Parse(e.to_string())
+    }
+}
+
+impl From<humantime::DurationError> for Error {
+    fn from(e: humantime::DurationError) -> Error {
+        Error::Parse(format!("duration: {}", e))
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Error {
+        Error::Invalid(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Error {
+        Error::Invalid(message.to_string())
+    }
+}
@@ -1,13 +1,22 @@
 #[macro_use]
 extern crate log;
 
+mod cli;
+mod clock;
+mod config;
+mod editor;
+mod error;
+mod report;
 mod state;
 mod storage;
 mod ui;
 mod timer;
+mod undo;
 
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+use structopt::StructOpt;
+
+pub use error::Error;
+pub type Result<T> = std::result::Result<T, Error>;
 
 fn main() {
     // set up a logger with default level 'info'
@@ -16,14 +25,28 @@ fn main() {
         .init();
 
     match run() {
-        Ok(_) => info!("done"),
-        Err(e) => error!("{}", e), // todo: print error properly
+        Ok(_) => debug!("done"),
+        Err(e) => {
+            eprintln!("timeknight: {}", e);
+            std::process::exit(1);
+        }
     }
 }
 
 fn run() -> Result<()> {
+    let mut opt = cli::Opt::from_args();
+    let mut config = config::load()?;
+    opt.apply(&mut config);
+    if let Some(dir) = &config.data_dir {
+        storage::set_data_dir(dir.clone());
+    }
+    if let Some(command) = opt.command.take() {
+        return cli::run(command, &config);
+    }
+
     // load app state from disk
-    let app = storage::load_state()?;
+    let mut app = storage::load_state()?;
+    app.configure(&config);
 
-    ui::run(app)
+    ui::run(app, config)
 }
@@ -0,0 +1,303 @@
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Datelike, Local};
+
+use crate::state::CompletedTask;
+
+/// Upper bounds (actual / estimate) and labels of the accuracy distribution buckets
+const BUCKETS: [(f64, &str); 5] = [
+    (0.5, "< 0.5x"),
+    (0.8, "0.5x - 0.8x"),
+    (1.2, "0.8x - 1.2x"),
+    (2.0, "1.2x - 2x"),
+    (f64::INFINITY, "> 2x"),
+];
+
+/// Estimation accuracy over a set of completed tasks
+pub struct Report {
+    /// Number of completed tasks looked at
+    pub completed: usize,
+    /// Number of those tasks which had an estimate
+    pub estimated: usize,
+    /// Number of completed tasks whose time was corrected by hand
+    pub adjusted: usize,
+    /// Number of sessions started by switching away from another task in exclusive mode
+    pub switches: usize,
+    /// Median of actual time / estimated time
+    pub median_ratio: Option<f64>,
+    /// Median of the absolute relative error, |actual - estimate| / estimate
+    pub median_error: Option<f64>,
+    /// Count of tasks per accuracy bucket, in the order of `BUCKETS`
+    pub buckets: Vec<(&'static str, usize)>,
+    /// Accuracy per ISO week, oldest first
+    pub weeks: Vec<WeekAccuracy>,
+    /// Time spent per project, most time first
+    pub projects: Vec<GroupTotal>,
+    /// Time spent per tag, most time first
+    pub tags: Vec<GroupTotal>,
+}
+
+/// Total time of the completed tasks in a project or with a tag
+pub struct GroupTotal {
+    pub name: String,
+    pub tasks: usize,
+    pub total_time: Duration,
+}
+
+pub struct WeekAccuracy {
+    pub year: i32,
+    pub week: u32,
+    pub estimated: usize,
+    pub median_ratio: f64,
+    pub median_error: f64,
+}
+
+impl Report {
+    pub fn new(tasks: &[CompletedTask]) -> Report {
+        let samples: Vec<(Option<SystemTime>, f64)> = tasks
+            .iter()
+            .filter_map(|task| {
+                let ratio = ratio(task.total_time, task.estimate?)?;
+                Some((task.completed_at, ratio))
+            })
+            .collect();
+        let ratios: Vec<f64> = samples.iter().map(|(_, ratio)| *ratio).collect();
+
+        let buckets = BUCKETS
+            .iter()
+            .enumerate()
+            .map(|(i, (upper, label))| {
+                let lower = if i == 0 { 0.0 } else { BUCKETS[i - 1].0 };
+                let count = ratios
+                    .iter()
+                    .filter(|ratio| **ratio >= lower && **ratio < *upper)
+                    .count();
+                (*label, count)
+            })
+            .collect();
+
+        let mut weeks: Vec<(i32, u32, Vec<f64>)> = Vec::new();
+        for (date, ratio) in &samples {
+            let week = match date {
+                Some(date) => DateTime::<Local>::from(*date).iso_week(),
+                None => continue,
+            };
+            match weeks
+                .iter_mut()
+                .find(|(year, number, _)| *year == week.year() && *number == week.week())
+            {
+                Some((_, _, ratios)) => ratios.push(*ratio),
+                None => weeks.push((week.year(), week.week(), vec![*ratio])),
+            }
+        }
+        weeks.sort_by_key(|(year, week, _)| (*year, *week));
+
+        let mut projects = Vec::new();
+        let mut tags = Vec::new();
+        for task in tasks {
+            if let Some(project) = &task.project {
+                add_to_group(&mut projects, project, task.total_time);
+            }
+            for tag in &task.tags {
+                add_to_group(&mut tags, tag, task.total_time);
+            }
+        }
+        projects.sort_by_key(|group| std::cmp::Reverse(group.total_time));
+        tags.sort_by_key(|group| std::cmp::Reverse(group.total_time));
+
+        Report {
+            completed: tasks.len(),
+            estimated: ratios.len(),
+            adjusted: tasks.iter().filter(|task| !task.adjustments.is_empty()).count(),
+            switches: tasks
+                .iter()
+                .flat_map(|task| &task.sessions)
+                .filter(|session| session.switched_from.is_some())
+                .count(),
+            median_ratio: median(&ratios),
+            median_error: median(&errors(&ratios)),
+            buckets,
+            weeks: weeks
+                .into_iter()
+                .filter_map(|(year, week, ratios)| {
+                    Some(WeekAccuracy {
+                        year,
+                        week,
+                        estimated: ratios.len(),
+                        median_ratio: median(&ratios)?,
+                        median_error: median(&errors(&ratios))?,
+                    })
+                })
+                .collect(),
+            projects,
+            tags,
+        }
+    }
+}
+
+fn add_to_group(groups: &mut Vec<GroupTotal>, name: &str, time: Duration) {
+    match groups.iter_mut().find(|group| group.name == name) {
+        Some(group) => {
+            group.tasks += 1;
+            group.total_time += time;
+        }
+        None => groups.push(GroupTotal {
+            name: name.to_string(),
+            tasks: 1,
+            total_time: time,
+        }),
+    }
+}
+
+/// Actual time over estimated time, or `None` for a zero estimate
+fn ratio(actual: Duration, estimate: Duration) -> Option<f64> {
+    if estimate.is_zero() {
+        None
+    } else {
+        Some(actual.as_secs_f64() / estimate.as_secs_f64())
+    }
+}
+
+fn errors(ratios: &[f64]) -> Vec<f64> {
+    ratios.iter().map(|ratio| (ratio - 1.0).abs()).collect()
+}
+
+fn median(values: &[f64]) -> Option<f64> {
+    let mut values = values.to_vec();
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    match values.len() {
+        0 => None,
+        n if n % 2 == 0 => Some((values[mid - 1] + values[mid]) / 2.0),
+        _ => Some(values[mid]),
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} completed quests, {} with estimates",
+            self.completed, self.estimated
+        )?;
+        if self.adjusted > 0 {
+            writeln!(f, "{} had their time adjusted by hand", self.adjusted)?;
+        }
+        if self.switches > 0 {
+            writeln!(f, "{} context switches between tasks", self.switches)?;
+        }
+        if let (Some(ratio), Some(error)) = (self.median_ratio, self.median_error) {
+            writeln!(f, "median actual/estimate: {:.2}x", ratio)?;
+            writeln!(f, "median error:           {:.0}%", error * 100.0)?;
+        }
+
+        writeln!(f, "\ndistribution (actual/estimate):")?;
+        let widest = self.buckets.iter().map(|(_, n)| *n).max().unwrap_or(0).max(1);
+        for (label, count) in &self.buckets {
+            writeln!(
+                f,
+                "  {:>12} {:>4} {}",
+                label,
+                count,
+                "█".repeat(count * 30 / widest)
+            )?;
+        }
+
+        writeln!(f, "\ntrend by week:")?;
+        for week in &self.weeks {
+            writeln!(
+                f,
+                "  {}-W{:02} {:>4} tasks  {:.2}x  {:.0}% error",
+                week.year,
+                week.week,
+                week.estimated,
+                week.median_ratio,
+                week.median_error * 100.0
+            )?;
+        }
+
+        for (title, prefix, groups) in [("project", "+", &self.projects), ("tag", "#", &self.tags)] {
+            if groups.is_empty() {
+                continue;
+            }
+            writeln!(f, "\ntime by {}:", title)?;
+            for group in groups {
+                writeln!(
+                    f,
+                    "  {:<20} {:>4} tasks  {}",
+                    format!("{}{}", prefix, group.name),
+                    group.tasks,
+                    humantime::format_duration(Duration::new(group.total_time.as_secs(), 0))
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::state::Task;
+
+    /// A quest that took `actual` minutes against an estimate of `estimate` minutes
+    fn quest(actual: u64, estimate: Option<u64>, completed_at: u64) -> CompletedTask {
+        let mut quest = CompletedTask::new(&Task::new("quest", &FakeClock::new()), &FakeClock::new());
+        quest.total_time = Duration::from_secs(actual * 60);
+        quest.estimate = estimate.map(|minutes| Duration::from_secs(minutes * 60));
+        quest.completed_at = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(completed_at));
+        quest
+    }
+
+    #[test]
+    fn medians_of_odd_and_even_counts() {
+        assert_eq!(median(&[]), None);
+        assert_eq!(median(&[3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(median(&[4.0, 1.0, 3.0, 2.0]), Some(2.5));
+    }
+
+    #[test]
+    fn zero_estimates_are_left_out_of_the_accuracy() {
+        assert_eq!(ratio(Duration::from_secs(60), Duration::ZERO), None);
+        let report = Report::new(&[quest(30, Some(0), 1_646_136_000), quest(30, None, 1_646_136_000)]);
+        assert_eq!(report.completed, 2);
+        assert_eq!(report.estimated, 0);
+        assert_eq!(report.median_ratio, None);
+        assert!(report.buckets.iter().all(|(_, count)| *count == 0));
+        assert!(report.weeks.is_empty());
+    }
+
+    #[test]
+    fn bucket_boundaries_belong_to_the_bucket_above() {
+        let at = 1_646_136_000;
+        let report = Report::new(&[
+            quest(49, Some(100), at),
+            quest(50, Some(100), at),
+            quest(100, Some(100), at),
+            quest(120, Some(100), at),
+            quest(200, Some(100), at),
+        ]);
+        let counts: Vec<usize> = report.buckets.iter().map(|(_, count)| *count).collect();
+        assert_eq!(counts, [1, 1, 1, 1, 1]);
+        assert_eq!(report.median_ratio, Some(1.0));
+        assert_eq!(report.median_error, Some(0.5));
+    }
+
+    #[test]
+    fn weeks_straddling_new_year_are_grouped_by_iso_week() {
+        let report = Report::new(&[
+            // noon UTC on Thursday 31 December 2020 and Friday 1 January 2021, both in 2020-W53
+            quest(60, Some(60), 1_609_416_000),
+            quest(90, Some(60), 1_609_502_400),
+            // Monday 4 January 2021
+            quest(30, Some(60), 1_609_761_600),
+        ]);
+        let weeks: Vec<(i32, u32, usize)> =
+            report.weeks.iter().map(|week| (week.year, week.week, week.estimated)).collect();
+        assert_eq!(weeks, [(2020, 53, 2), (2021, 1, 1)]);
+        assert_eq!(report.weeks[0].median_ratio, 1.25);
+        assert_eq!(report.weeks[1].median_error, 0.5);
+    }
+}
@@ -1,137 +1,1705 @@
-use std::time::Duration;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
 
+use chrono::{DateTime, Local, Timelike};
 use serde::{Deserialize, Serialize};
 
+use crate::clock::{Clock, SystemClock};
+use crate::config::CountdownEnd;
+use crate::editor::LineEditor;
+use crate::timer::{Countdown, Stopwatch};
+use crate::undo::{QuestState, Snapshot, UndoStack};
+
 /// App holds the state of the application
 pub struct App {
-    /// Current value of the input box
-    pub input: String,
+    /// The input box
+    pub input: LineEditor,
     /// Current input mode
     pub input_mode: InputMode,
     /// History of recorded tasks
     pub tasks: Vec<Task>,
     /// Currently selected task
     pub selected_task: usize,
+    /// Only tasks matching this are listed, see `Task::matches`
+    pub filter: String,
+    /// Which screen the TUI is showing
+    pub screen: Screen,
+    /// Completed quests, loaded when the history screen is opened
+    pub history: History,
+    /// Undo/redo history of changes to `tasks`
+    pub undo: UndoStack,
+    /// Set while another instance owns the data directory; changes are refused
+    pub read_only: bool,
+    /// Modification time of `active.ron` when it was last loaded or saved by us, to notice
+    /// when another process has written it
+    pub synced: Cell<Option<SystemTime>>,
+    /// Where timers get the time from
+    pub clock: Rc<dyn Clock>,
+    /// Wall-clock time at the last `check_clock`, to notice the clock being set back
+    pub last_seen: Option<SystemTime>,
+    /// Only one timer runs at a time: starting a task stops whichever one was running
+    pub exclusive: bool,
+    /// How long to work each day, shown as a gauge in the header
+    pub daily_goal: Option<Duration>,
+    /// Session intervals of the quests completed today, for `today_elapsed`
+    pub completed_today: Vec<(SystemTime, SystemTime)>,
+    /// Time corrections made to the quests completed today, for `today_elapsed`
+    pub completed_adjustments: Vec<TimeAdjustment>,
+    /// Modification time of `completed_quests` when `completed_today` was loaded
+    pub completed_synced: Option<SystemTime>,
+    /// The last failure and when it happened, shown in place of the help bar for a while
+    pub status: Option<(String, SystemTime)>,
+    /// What countdown tasks do when their time is up
+    pub countdown_end: CountdownEnd,
+    /// When a countdown last ran out, to flash the exhausted tasks for a moment
+    pub countdown_alert: Option<SystemTime>,
+    /// Suggest a break after a timer has run this long without stopping
+    pub break_reminder: Option<Duration>,
+    /// When the break reminder was last dismissed, which puts it off for another interval
+    pub reminder_dismissed: Option<SystemTime>,
 }
 
+/// How long a failure stays in the status line
+const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+
 impl App {
-    pub fn add_task(&mut self, description: &str) -> Result<(), crate::Error> {
-        self.tasks.push(Task {
-            description: description.into(),
-            timer: crate::timer::Stopwatch::start_new(),
-        });
+    /// Add a new running task. A trailing `~<duration>` (e.g. `Fix login bug ~45m`) is
+    /// taken as the time estimate for the task, and `+project` and `#tag` words anywhere in
+    /// the description are taken out as its project and tags.
+    pub fn add_task(&mut self, input: &str) -> Result<(), crate::Error> {
+        let (description, estimate) = parse_estimate(input);
+        let (description, project, tags) = parse_tags(description);
+        let mut task = Task::new(&description, self.clock.as_ref());
+        task.estimate = estimate;
+        task.project = project;
+        task.tags = tags;
+        self.checkpoint("add")?;
+        self.tasks.push(task);
+        self.switch_to(self.tasks.len() - 1);
         self.input_mode = InputMode::Normal;
-        crate::storage::save_state(&self)
+        crate::storage::save_state(self)
+    }
+
+    /// Apply the behaviour settings of the config file (and command line)
+    pub fn configure(&mut self, config: &crate::config::Config) {
+        self.exclusive = config.exclusive;
+        self.daily_goal = Some(config.daily_goal).filter(|goal| !goal.is_zero());
+        self.countdown_end = config.countdown_end;
+        self.break_reminder = Some(config.break_reminder).filter(|every| !every.is_zero());
     }
 
-    /// Total time spent on all active tasks
+    /// Open the input box for a new task, offering earlier task descriptions for recall and
+    /// completion
+    pub fn start_adding(&mut self) {
+        let mut quests = crate::storage::load_completed_tasks().unwrap_or_default();
+        quests.sort_by_key(|quest| quest.completed_at);
+        let mut history: Vec<String> = Vec::new();
+        let descriptions = quests
+            .into_iter()
+            .map(|quest| quest.description)
+            .chain(self.tasks.iter().map(|task| task.description.clone()));
+        for description in descriptions {
+            history.retain(|entry| *entry != description);
+            history.push(description);
+        }
+        self.input.set_history(history);
+        self.input_mode = InputMode::Editing;
+    }
+
+    /// Open the input box on the selected task's description, project, tags and estimate for
+    /// editing
+    pub fn start_renaming(&mut self) {
+        if let Some(task) = self.tasks.get(self.selected_task) {
+            let mut text = task.description.clone();
+            if let Some(project) = &task.project {
+                text.push_str(&format!(" +{}", project));
+            }
+            for tag in &task.tags {
+                text.push_str(&format!(" #{}", tag));
+            }
+            if let Some(estimate) = task.estimate {
+                text.push_str(&format!(" ~{}", humantime::format_duration(estimate)));
+            }
+            self.input.set(&text);
+            self.input_mode = InputMode::Renaming;
+        }
+    }
+
+    /// Change the selected task's description. As with `add_task`, a trailing `~<duration>`
+    /// sets the estimate and `+project`/`#tag` words set the project and tags; leaving them
+    /// off removes them.
+    pub fn rename_selected_task(&mut self, input: &str) -> Result<(), crate::Error> {
+        let (description, estimate) = parse_estimate(input);
+        let (description, project, tags) = parse_tags(description);
+        if description.is_empty() {
+            return Err("a task needs a description".into());
+        }
+        if self.tasks.get(self.selected_task).is_none() {
+            return Ok(());
+        }
+        self.checkpoint("rename")?;
+        let task = &mut self.tasks[self.selected_task];
+        task.description = description;
+        task.estimate = estimate;
+        task.project = project;
+        task.tags = tags;
+        crate::storage::save_state(self)
+    }
+
+    /// Total time spent on all active tasks, counting time during which several timers were
+    /// running only once
     pub fn active_elapsed(&self) -> Duration {
-        self.tasks
+        let now = self.clock.now();
+        let total = self
+            .work()
+            .fold(Duration::new(0, 0), |acc, task| acc + task.timer.elapsed(self.clock.as_ref()));
+        let intervals: Vec<(SystemTime, SystemTime)> = self
+            .work()
+            .flat_map(|task| task.sessions.iter().map(|session| session.interval(now)))
+            .collect();
+        let summed = intervals
             .iter()
-            .fold(Duration::new(0, 0), |acc, task| acc + task.timer.elapsed())
+            .fold(Duration::new(0, 0), |acc, (start, end)| {
+                acc + end.duration_since(*start).unwrap_or_default()
+            });
+        total.saturating_sub(summed.saturating_sub(union_length(intervals)))
+    }
+
+    /// Time worked today, from the sessions of active tasks and of quests completed today,
+    /// corrected by the adjustments made to them today. Time during which several timers
+    /// were running counts once.
+    pub fn today_elapsed(&self) -> Duration {
+        let now = self.clock.now();
+        let midnight = start_of_day(now);
+        let intervals = self
+            .work()
+            .flat_map(|task| task.sessions.iter().map(|session| session.interval(now)))
+            .chain(self.completed_today.iter().copied())
+            .filter(|(_, end)| *end > midnight)
+            .map(|(start, end)| (start.max(midnight), end))
+            .collect();
+        let (added, removed) = self
+            .work()
+            .flat_map(|task| task.adjustments.iter())
+            .chain(self.completed_adjustments.iter())
+            .filter(|adjustment| adjustment.at >= midnight)
+            .fold((Duration::ZERO, Duration::ZERO), |(added, removed), adjustment| {
+                (
+                    added + adjustment.after.saturating_sub(adjustment.before),
+                    removed + adjustment.before.saturating_sub(adjustment.after),
+                )
+            });
+        (union_length(intervals) + added).saturating_sub(removed)
+    }
+
+    /// Whether the task shows in the timer list: it matches the filter and isn't inside a
+    /// folded task
+    pub fn is_listed(&self, index: usize) -> bool {
+        self.tasks[index].matches(&self.filter)
+            && !self.ancestors(index).iter().any(|&i| self.tasks[i].collapsed)
+    }
+
+    /// Indices of the task a subtask is part of, that task's parent and so on up
+    fn ancestors(&self, index: usize) -> Vec<usize> {
+        let mut ancestors = Vec::new();
+        let mut parent = self.tasks[index].parent;
+        while let Some(i) = parent.and_then(|id| self.tasks.iter().position(|task| task.id == Some(id))) {
+            // an edited file could link tasks in a circle
+            if i == index || ancestors.contains(&i) {
+                break;
+            }
+            ancestors.push(i);
+            parent = self.tasks[i].parent;
+        }
+        ancestors
+    }
+
+    /// How deep in the tree of subtasks the task is, 0 for a top level task
+    pub fn depth(&self, index: usize) -> usize {
+        self.ancestors(index).len()
+    }
+
+    /// Whether the task has been split into subtasks which are still active
+    pub fn has_subtasks(&self, index: usize) -> bool {
+        let id = self.tasks[index].id;
+        id.is_some() && self.tasks.iter().any(|task| task.parent == id)
+    }
+
+    /// Whether a timer is running on one of the task's subtasks, at any depth
+    pub fn subtask_running(&self, index: usize) -> bool {
+        (0..self.tasks.len())
+            .any(|i| self.tasks[i].timer.is_running() && self.ancestors(i).contains(&index))
+    }
+
+    /// The task and all its subtasks, at any depth
+    fn subtree(&self, index: usize) -> Vec<usize> {
+        (0..self.tasks.len()).filter(|&i| i == index || self.ancestors(i).contains(&index)).collect()
+    }
+
+    /// Time spent on a task and all its subtasks, and the sum of their estimates
+    pub fn rollup(&self, index: usize) -> (Duration, Option<Duration>) {
+        let clock = self.clock.as_ref();
+        self.subtree(index).into_iter().fold((Duration::ZERO, None), |(elapsed, estimate), i| {
+            let task = &self.tasks[i];
+            let estimate = match (estimate, task.estimate) {
+                (Some(total), Some(own)) => Some(total + own),
+                (total, own) => total.or(own),
+            };
+            (elapsed + task.timer.elapsed(clock), estimate)
+        })
+    }
+
+    /// Break the selected task into subtasks, one for each comma separated part of `input`,
+    /// which are read like `add_task` input. The time spent so far stays with the task, which
+    /// then shows the total of its parts, and a running timer carries on in the first part.
+    /// Subtasks with estimates take over the task's own estimate.
+    pub fn split_selected_task(&mut self, input: &str) -> Result<(), crate::Error> {
+        let parts: Vec<&str> = input.split(',').map(str::trim).filter(|part| !part.is_empty()).collect();
+        match self.tasks.get(self.selected_task) {
+            None => return Ok(()),
+            Some(task) if task.is_break => return Err("breaks can't be split".into()),
+            Some(_) if parts.is_empty() => {
+                return Err("list the subtasks separated by commas".into())
+            }
+            Some(_) => {}
+        }
+        self.checkpoint("split")?;
+        let clock = self.clock.clone();
+        let index = self.selected_task;
+        let id = match self.tasks[index].id {
+            Some(id) => id,
+            None => {
+                let id = self.tasks.iter().filter_map(|task| task.id).max().map_or(1, |id| id + 1);
+                self.tasks[index].id = Some(id);
+                id
+            }
+        };
+
+        let parent = &mut self.tasks[index];
+        let was_running = parent.timer.is_running();
+        parent.stop(clock.as_ref());
+        parent.collapsed = false;
+        let mut subtasks = Vec::new();
+        for (i, part) in parts.iter().enumerate() {
+            let (description, estimate) = parse_estimate(part);
+            let (description, project, tags) = parse_tags(description);
+            let mut task = Task::new(&description, clock.as_ref());
+            if i > 0 || !was_running {
+                task.stop(clock.as_ref());
+                task.sessions.clear();
+            }
+            task.estimate = estimate;
+            task.project = project.or_else(|| parent.project.clone());
+            task.tags = if tags.is_empty() { parent.tags.clone() } else { tags };
+            task.parent = Some(id);
+            subtasks.push(task);
+        }
+        if subtasks.iter().any(|task| task.estimate.is_some()) {
+            parent.estimate = None;
+        }
+
+        // subtasks go after the ones the task already has
+        let at = (index + 1..self.tasks.len())
+            .find(|&i| !self.ancestors(i).contains(&index))
+            .unwrap_or(self.tasks.len());
+        self.tasks.splice(at..at, subtasks);
+        self.selected_task = at;
+        crate::storage::save_state(self)
+    }
+
+    /// Fold or unfold the selected task's subtasks. On a subtask, folds the task it's part of.
+    pub fn toggle_fold_selected_task(&mut self) -> Result<(), crate::Error> {
+        if self.tasks.get(self.selected_task).is_none() {
+            return Ok(());
+        }
+        let index = match self.has_subtasks(self.selected_task) {
+            true => self.selected_task,
+            false => match self.ancestors(self.selected_task).first() {
+                Some(&parent) => parent,
+                None => return Ok(()),
+            },
+        };
+        self.tasks[index].collapsed = !self.tasks[index].collapsed;
+        self.selected_task = index;
+        crate::storage::save_state(self)
+    }
+
+    /// Take a task out of the list. Its subtasks move up to whatever it was part of.
+    fn remove_task(&mut self, index: usize) -> Task {
+        let task = self.tasks.remove(index);
+        if task.id.is_some() {
+            for subtask in self.tasks.iter_mut().filter(|subtask| subtask.parent == task.id) {
+                subtask.parent = task.parent;
+            }
+        }
+        task
+    }
+
+    /// Active tasks other than the break timer, whose time isn't work
+    fn work(&self) -> impl Iterator<Item = &Task> {
+        self.tasks.iter().filter(|task| !task.is_break)
+    }
+
+    /// How long the longest running timer has been going without a stop
+    pub fn focused_for(&self) -> Option<Duration> {
+        self.work()
+            .filter_map(|task| task.timer.current_run(self.clock.as_ref()))
+            .max()
+    }
+
+    /// How long the user has been focused, if that's past `break_reminder` and the reminder
+    /// hasn't been dismissed within the last interval
+    pub fn break_due(&self) -> Option<Duration> {
+        let every = self.break_reminder?;
+        let focused = self.focused_for().filter(|focused| *focused >= every)?;
+        let now = self.clock.now();
+        match self.reminder_dismissed {
+            Some(at) if now.duration_since(at).unwrap_or_default() < every => None,
+            _ => Some(focused),
+        }
+    }
+
+    /// Hide the break reminder for another `break_reminder`
+    pub fn dismiss_break_reminder(&mut self) {
+        self.reminder_dismissed = Some(self.clock.now());
+    }
+
+    /// Start a break, stopping the running timers, or end the break in progress and restart
+    /// the timers it stopped. Breaks are timed by a task of their own, added when needed.
+    pub fn toggle_break(&mut self) -> Result<(), crate::Error> {
+        self.checkpoint("break")?;
+        let clock = self.clock.clone();
+        let index = match self.tasks.iter().position(|task| task.is_break) {
+            Some(index) => index,
+            None => {
+                let mut task = Task::new("Break", clock.as_ref());
+                task.stop(clock.as_ref());
+                task.sessions.clear();
+                task.is_break = true;
+                self.tasks.push(task);
+                self.tasks.len() - 1
+            }
+        };
+
+        if self.tasks[index].timer.is_running() {
+            self.tasks[index].stop(clock.as_ref());
+            for task in self.tasks.iter_mut().filter(|task| task.paused_by_break) {
+                task.paused_by_break = false;
+                task.start(clock.as_ref());
+            }
+        } else {
+            for task in self.tasks.iter_mut().filter(|task| task.timer.is_running()) {
+                task.stop(clock.as_ref());
+                task.paused_by_break = true;
+            }
+            self.tasks[index].start(clock.as_ref());
+        }
+        self.reminder_dismissed = None;
+        crate::storage::save_state(self)
+    }
+
+    /// Outside a break nothing is waiting for one to end. Undo or another process can leave
+    /// tasks marked that way, which would otherwise restart with the next break.
+    fn forget_stale_break(&mut self) {
+        if !self.tasks.iter().any(|task| task.is_break && task.timer.is_running()) {
+            for task in &mut self.tasks {
+                task.paused_by_break = false;
+            }
+        }
+    }
+
+    /// Reload `completed_today` if quests have been completed or reopened since it was loaded
+    pub fn refresh_completed_today(&mut self) -> Result<(), crate::Error> {
+        // `None` until the first quest is completed, which is no reason to keep reloading
+        let modified = crate::storage::completed_modified();
+        if modified == self.completed_synced {
+            return Ok(());
+        }
+        let midnight = start_of_day(self.clock.now());
+        let quests = crate::storage::load_completed_tasks()?;
+        let completed_today: Vec<_> = quests
+            .iter()
+            .filter_map(|quest| Some((quest, quest.completed_at?)))
+            .filter(|(_, completed_at)| *completed_at >= midnight)
+            .collect();
+        self.completed_today = completed_today
+            .iter()
+            .flat_map(|&(quest, completed_at)| {
+                quest.sessions.iter().map(move |session| session.interval(completed_at))
+            })
+            .collect();
+        self.completed_adjustments =
+            completed_today.iter().flat_map(|(quest, _)| quest.adjustments.iter().copied()).collect();
+        self.completed_synced = modified;
+        Ok(())
     }
 
     /// Save application state to disk
     pub fn save(&self) -> Result<(), crate::Error> {
-        crate::storage::save_state(&self)
+        crate::storage::save_state(self)
     }
 
+    /// Select the previous task in the list
     pub fn move_up(&mut self) {
-        if self.selected_task > 0 {
-            self.selected_task -= 1;
+        if let Some(index) = (0..self.selected_task)
+            .rev()
+            .find(|&i| self.is_listed(i))
+        {
+            self.selected_task = index;
         }
     }
 
+    /// Select the next task in the list
     pub fn move_down(&mut self) {
-        if self.selected_task < self.tasks.len() - 1 {
-            self.selected_task += 1;
+        if let Some(index) = (self.selected_task + 1..self.tasks.len())
+            .find(|&i| self.is_listed(i))
+        {
+            self.selected_task = index;
+        }
+    }
+
+    /// Whether the selected task is in the list, so actions on it make sense
+    pub fn selection_visible(&self) -> bool {
+        self.selected_task < self.tasks.len() && self.is_listed(self.selected_task)
+    }
+
+    /// After the filter changed, move the selection to the first task it shows if the
+    /// selected one is hidden
+    pub fn select_visible(&mut self) {
+        if !self.selection_visible() {
+            if let Some(index) = (0..self.tasks.len()).find(|&i| self.is_listed(i)) {
+                self.selected_task = index;
+            }
         }
     }
 
     pub fn delete_selected_task(&mut self) -> Result<(), crate::Error> {
-        if let Some(_) = self.tasks.get_mut(self.selected_task) {
-            self.tasks.remove(self.selected_task);
-            crate::storage::save_state(&self)
+        if self.tasks.get(self.selected_task).is_some() {
+            self.checkpoint("delete")?;
+            self.remove_task(self.selected_task);
+            crate::storage::save_state(self)
         } else {
-            unimplemented!();
+            Ok(())
         }
     }
 
+    /// Stop every running timer, returning the indices of the tasks stopped
+    pub fn stop_all(&mut self) -> Result<Vec<usize>, crate::Error> {
+        let running: Vec<usize> = (0..self.tasks.len()).filter(|&i| self.tasks[i].timer.is_running()).collect();
+        if running.is_empty() {
+            return Ok(running);
+        }
+        self.checkpoint("stop")?;
+        for &i in &running {
+            self.tasks[i].stop(self.clock.as_ref());
+        }
+        crate::storage::save_state(self)?;
+        Ok(running)
+    }
+
     pub fn toggle_play_pause_selected_task(&mut self) -> Result<(), crate::Error> {
-        if let Some(task) = self.tasks.get_mut(self.selected_task) {
+        if self.tasks.get(self.selected_task).is_some_and(|task| task.is_break) {
+            self.toggle_break()
+        } else if self.tasks.get(self.selected_task).is_some() {
+            self.checkpoint("toggle")?;
+            let task = &mut self.tasks[self.selected_task];
             if task.timer.is_running() {
-                task.timer.stop();
+                task.stop(self.clock.as_ref());
             } else {
-                task.timer.start();
+                task.start(self.clock.as_ref());
+                self.switch_to(self.selected_task);
             }
-            crate::storage::save_state(&self)
+            crate::storage::save_state(self)
         } else {
-            unimplemented!();
+            Ok(())
         }
     }
 
     pub fn complete_selected_task(&mut self) -> Result<(), crate::Error> {
-        if let Some(task) = self.tasks.get(self.selected_task) {
-            crate::storage::save_completed_task(task);
-            self.tasks.remove(self.selected_task);
-            crate::storage::save_state(&self)
+        if self.tasks.get(self.selected_task).is_some_and(|task| task.is_break) {
+            Err("breaks can't be completed, delete the break timer to clear them".into())
+        } else if self.tasks.get(self.selected_task).is_some() {
+            self.checkpoint("complete")?;
+            let task = &mut self.tasks[self.selected_task];
+            task.complete(self.clock.as_ref());
+            let mut quest = CompletedTask::new(task, self.clock.as_ref());
+            quest.id = crate::storage::save_completed_task(quest.clone())?;
+            self.undo.set_last_quest(QuestState {
+                quest,
+                archived: false,
+            });
+            self.remove_task(self.selected_task);
+            crate::storage::save_state(self)
         } else {
-            unimplemented!();
+            Ok(())
         }
     }
 
     pub fn reset_selected_task(&mut self) -> Result<(), crate::Error> {
-        if let Some(task) = self.tasks.get_mut(self.selected_task) {
-            task.timer.reset();
-            crate::storage::save_state(&self)
+        if self.tasks.get(self.selected_task).is_some() {
+            self.checkpoint("reset")?;
+            self.tasks[self.selected_task].reset();
+            crate::storage::save_state(self)
         } else {
-            unimplemented!();
+            Ok(())
+        }
+    }
+
+    /// Where a countdown task stands against its estimate, or the estimates of its parts once
+    /// split, `None` for tasks counting up
+    pub fn countdown(&self, index: usize) -> Option<Countdown> {
+        if !self.tasks[index].countdown {
+            return None;
+        }
+        let (elapsed, estimate) = self.rollup(index);
+        Some(Countdown::new(elapsed, estimate?))
+    }
+
+    /// Switch the selected task between counting up and counting down from its estimate
+    pub fn toggle_countdown_selected_task(&mut self) -> Result<(), crate::Error> {
+        match self.tasks.get(self.selected_task) {
+            Some(_) if self.rollup(self.selected_task).1.is_none() => {
+                Err("add an estimate (e.g. ~45m) to count down from".into())
+            }
+            Some(_) => {
+                self.checkpoint("countdown")?;
+                let task = &mut self.tasks[self.selected_task];
+                task.countdown = !task.countdown;
+                crate::storage::save_state(self)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Deal with the countdown timers which ran out after `since`: flash them and, if
+    /// `countdown_end` says so, stop them at their estimate. A split task runs out when its
+    /// parts do, which stops them all. Returns whether any ran out.
+    pub fn end_countdowns(&mut self, since: SystemTime) -> Result<bool, crate::Error> {
+        let now = self.clock.now();
+        let ended: Vec<(Vec<usize>, SystemTime)> = (0..self.tasks.len())
+            .filter(|&i| self.tasks[i].countdown)
+            .filter_map(|i| {
+                let subtree = self.subtree(i);
+                let timers: Vec<&Stopwatch> = subtree.iter().map(|&j| &self.tasks[j].timer).collect();
+                let at = Stopwatch::runs_out_at(&timers, self.rollup(i).1?)?;
+                Some((subtree, at))
+            })
+            .filter(|&(_, at)| since < at && at <= now)
+            .collect();
+        if ended.is_empty() {
+            return Ok(false);
+        }
+        self.countdown_alert = Some(now);
+        if self.countdown_end == CountdownEnd::Pause && !self.read_only {
+            self.checkpoint("countdown end")?;
+            for (subtree, at) in ended {
+                for i in subtree {
+                    if self.tasks[i].timer.is_running() {
+                        self.tasks[i].stop_at(at, self.clock.as_ref());
+                    }
+                }
+            }
+            crate::storage::save_state(self)?;
+        }
+        Ok(true)
+    }
+
+    /// Switch to the history screen, reloading completed quests from disk
+    pub fn show_history(&mut self) -> Result<(), crate::Error> {
+        let mut quests = crate::storage::load_completed_tasks()?;
+        quests.sort_by_key(|quest| std::cmp::Reverse(quest.completed_at));
+        self.history.quests = quests;
+        self.history.selected = 0;
+        self.screen = Screen::History;
+        Ok(())
+    }
+
+    /// Move the selected completed quest back into the active tasks
+    pub fn reopen_selected_quest(&mut self) -> Result<(), crate::Error> {
+        let id = match self.history.visible().get(self.history.selected) {
+            Some(quest) => quest.id.clone(),
+            None => return Ok(()),
+        };
+        let index = self.history.quests.iter().position(|quest| quest.id == id);
+        if let Some(index) = index {
+            // nothing is touched until we know the change can be saved, and neither the
+            // history nor the undo stack changes unless the quest's file is gone
+            self.ensure_writable()?;
+            crate::storage::delete_completed_task(&id)?;
+            self.checkpoint("reopen")?;
+            let quest = self.history.quests.remove(index);
+            self.undo.set_last_quest(QuestState {
+                quest: quest.clone(),
+                archived: true,
+            });
+            self.tasks.push(Task::from(quest));
+            self.selected_task = self.tasks.len() - 1;
+            self.history.move_up_if_past_end();
+            crate::storage::save_state(self)?;
+        }
+        Ok(())
+    }
+
+    /// Notice the wall clock having been set back and shift running timers to match, so they
+    /// neither lose time nor show a start in the future. Returns the largest correction made.
+    pub fn check_clock(&mut self) -> Option<Duration> {
+        let now = self.clock.now();
+        let skew = self
+            .last_seen
+            .and_then(|last| last.duration_since(now).ok())
+            .filter(|skew| !skew.is_zero());
+        self.last_seen = Some(now);
+
+        let mut correction = skew;
+        for task in &mut self.tasks {
+            if !task.timer.is_running() {
+                continue;
+            }
+            if let Some(skew) = skew {
+                task.rewind_skew(skew);
+            }
+            // a run starting in the future means the clock went back while we weren't
+            // watching; how far is unknown so the run restarts from now
+            let ahead = task
+                .timer
+                .start_time()
+                .and_then(|start| start.duration_since(now).ok())
+                .filter(|ahead| !ahead.is_zero());
+            if let Some(ahead) = ahead {
+                task.rewind_skew(ahead);
+                correction = correction.max(Some(ahead));
+            }
+        }
+        correction
+    }
+
+    /// Stop a task, throwing away the time since it was last started
+    pub fn discard_current_run(&mut self, index: usize) -> Result<(), crate::Error> {
+        if self.tasks.get(index).is_some() {
+            self.checkpoint("discard run")?;
+            self.tasks[index].discard_run();
+            crate::storage::save_state(self)?;
+        }
+        Ok(())
+    }
+
+    /// Take the time between `from` and `to`, when nobody was at the keyboard, off every
+    /// running timer
+    pub fn discard_away_time(&mut self, from: SystemTime, to: SystemTime) -> Result<(), crate::Error> {
+        self.checkpoint("discard away time")?;
+        for task in self.tasks.iter_mut().filter(|task| !task.is_break) {
+            task.skip(from, to);
+        }
+        crate::storage::save_state(self)
+    }
+
+    /// Move the time between `from` and `to` off the running timers and onto the task at
+    /// `index`, e.g. after forgetting to switch timers before a meeting
+    pub fn reassign_away_time(
+        &mut self,
+        from: SystemTime,
+        to: SystemTime,
+        index: usize,
+    ) -> Result<(), crate::Error> {
+        if self.tasks.get(index).is_none() {
+            return Ok(());
+        }
+        self.checkpoint("reassign away time")?;
+        for task in self.tasks.iter_mut().filter(|task| !task.is_break) {
+            task.skip(from, to);
+        }
+        self.tasks[index].add_time(from, to);
+        crate::storage::save_state(self)
+    }
+
+    /// Correct the selected task's time, from input such as `+15m`, `-1h30m` or `=2h`
+    pub fn adjust_selected_task(&mut self, input: &str) -> Result<(), crate::Error> {
+        let adjustment: Adjustment = input.parse()?;
+        if self.tasks.get(self.selected_task).is_none() {
+            return Ok(());
+        }
+        self.checkpoint("adjust")?;
+        self.tasks[self.selected_task].adjust(adjustment, self.clock.as_ref());
+        crate::storage::save_state(self)
+    }
+
+    /// In exclusive mode, stop every timer but the one at `index`, which has just been
+    /// started, noting which task it took over from
+    fn switch_to(&mut self, index: usize) {
+        // getting back to work ends a break
+        for task in self.tasks.iter_mut().filter(|task| task.is_break) {
+            task.stop(self.clock.as_ref());
+        }
+        self.forget_stale_break();
+        if !self.exclusive {
+            return;
+        }
+        let mut switched_from = None;
+        for (i, task) in self.tasks.iter_mut().enumerate() {
+            if i != index && task.timer.is_running() {
+                task.stop(self.clock.as_ref());
+                switched_from = Some(task.description.clone());
+            }
+        }
+        if let Some(session) = self.tasks[index].sessions.last_mut() {
+            session.switched_from = switched_from;
+        }
+    }
+
+    /// Show the error of a failed action in the status line instead of dropping it
+    pub fn report<T>(&mut self, result: Result<T, crate::Error>) {
+        if let Err(e) = result {
+            self.status = Some((e.to_string(), self.clock.now()));
+        }
+    }
+
+    /// The failure to show in the status line, until it has been up for `STATUS_TIMEOUT`
+    pub fn status(&self) -> Option<&str> {
+        let (message, at) = self.status.as_ref()?;
+        let shown_for = self.clock.now().duration_since(*at).unwrap_or_default();
+        (shown_for < STATUS_TIMEOUT).then_some(message.as_str())
+    }
+
+    /// Revert the most recent action
+    pub fn undo(&mut self) -> Result<(), crate::Error> {
+        self.ensure_writable()?;
+        let (tasks, selected_task) = (&self.tasks, self.selected_task);
+        match self.undo.undo(|snapshot| snapshot.counterpart(tasks, selected_task)) {
+            Some(snapshot) => self.restore(snapshot),
+            None => Ok(()),
+        }
+    }
+
+    /// Re-apply the most recently undone action
+    pub fn redo(&mut self) -> Result<(), crate::Error> {
+        self.ensure_writable()?;
+        let (tasks, selected_task) = (&self.tasks, self.selected_task);
+        match self.undo.redo(|snapshot| snapshot.counterpart(tasks, selected_task)) {
+            Some(snapshot) => self.restore(snapshot),
+            None => Ok(()),
+        }
+    }
+
+    /// Remember the current tasks so the action about to happen can be undone
+    fn checkpoint(&mut self, action: &str) -> Result<(), crate::Error> {
+        self.ensure_writable()?;
+        self.undo.push(Snapshot {
+            action: action.into(),
+            tasks: self.tasks.clone(),
+            selected_task: self.selected_task,
+            quest: None,
+        });
+        Ok(())
+    }
+
+    fn ensure_writable(&self) -> Result<(), crate::Error> {
+        if self.read_only {
+            return Err(crate::Error::ReadOnly);
+        }
+        Ok(())
+    }
+
+    /// Pick up changes another process (e.g. a `timeknight stop` from a git hook) made to the
+    /// saved state. Their write started from our last save, so taking it wholesale merges both.
+    pub fn reload_if_changed(&mut self) -> Result<bool, crate::Error> {
+        if !crate::storage::reload_state(self)? {
+            return Ok(false);
+        }
+        self.forget_stale_break();
+        self.selected_task = self.selected_task.min(self.tasks.len().saturating_sub(1));
+        Ok(true)
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) -> Result<(), crate::Error> {
+        self.tasks = snapshot.tasks;
+        self.selected_task = snapshot.selected_task.min(self.tasks.len().saturating_sub(1));
+        self.forget_stale_break();
+        if let Some(state) = snapshot.quest {
+            if state.archived {
+                crate::storage::save_completed_task(state.quest)?;
+            } else {
+                crate::storage::delete_completed_task(&state.quest.id)?;
+            }
         }
+        crate::storage::save_state(self)
     }
 }
 
 pub enum InputMode {
     Normal,
     Editing,
+    /// Typing a time correction for the selected task
+    Adjusting,
+    /// Editing the selected task's description
+    Renaming,
+    /// Typing the subtasks to split the selected task into
+    Splitting,
+    /// Typing a search query on the history screen
+    Searching,
+}
+
+impl InputMode {
+    /// Whether keys go to the line editor, as they do when adding, adjusting, renaming or
+    /// splitting a task
+    pub fn is_text_entry(&self) -> bool {
+        matches!(self, InputMode::Editing | InputMode::Adjusting | InputMode::Renaming | InputMode::Splitting)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Screen {
+    Timers,
+    History,
+}
+
+/// Completed quests as browsed on the history screen
+#[derive(Default)]
+pub struct History {
+    /// Newest first
+    pub quests: Vec<CompletedTask>,
+    /// Index into `visible()`
+    pub selected: usize,
+    /// Case-insensitive filter on quest descriptions, projects and tags
+    pub query: String,
+}
+
+impl History {
+    /// Quests matching the current search query, newest first
+    pub fn visible(&self) -> Vec<&CompletedTask> {
+        let query = self.query.to_lowercase();
+        self.quests
+            .iter()
+            .filter(|quest| {
+                quest.description.to_lowercase().contains(&query)
+                    || quest.project.iter().any(|project| project.to_lowercase().contains(&query))
+                    || quest.tags.iter().any(|tag| tag.to_lowercase().contains(&query))
+            })
+            .collect()
+    }
+
+    pub fn move_up(&mut self) {
+        if self.selected > 0 {
+            self.selected -= 1;
+        }
+    }
+
+    pub fn move_down(&mut self) {
+        if self.selected + 1 < self.visible().len() {
+            self.selected += 1;
+        }
+    }
+
+    /// Keep the selection on a visible quest after the list shrinks
+    pub fn move_up_if_past_end(&mut self) {
+        self.selected = self.selected.min(self.visible().len().saturating_sub(1));
+    }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Task {
     pub description: String,
     pub timer: crate::timer::Stopwatch,
+    /// Every start/stop interval of the timer, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sessions: Vec<Session>,
+    /// How long the task was expected to take
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate: Option<Duration>,
+    /// When the task was added, `None` for tasks saved before this was tracked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<SystemTime>,
+    /// Project given as `+name` when the task was added
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// Tags given as `#name` when the task was added
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Manual corrections to the tracked time, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub adjustments: Vec<TimeAdjustment>,
+    /// Show the time left of the estimate rather than the time spent
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub countdown: bool,
+    /// Whether this is the break timer, whose time is kept out of the totals
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_break: bool,
+    /// Stopped by the break in progress, to be restarted when it ends
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub paused_by_break: bool,
+    /// Identifies a task which has been split, for its subtasks to refer to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    /// `id` of the task this is a subtask of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<u32>,
+    /// Hide the subtasks in the timer list
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub collapsed: bool,
 }
 
-
 impl Task {
-    pub fn complete(&mut self) -> Result<(), crate::Error> {
-        // self.timer
-        Ok(())
+    /// Create a new task with its timer already running
+    pub fn new(description: &str, clock: &dyn Clock) -> Task {
+        let now = clock.now();
+        Task {
+            description: description.into(),
+            timer: crate::timer::Stopwatch::start_new(clock),
+            sessions: vec![Session {
+                start: now,
+                end: None,
+                switched_from: None,
+            }],
+            estimate: None,
+            created_at: Some(now),
+            project: None,
+            tags: Vec::new(),
+            adjustments: Vec::new(),
+            countdown: false,
+            is_break: false,
+            paused_by_break: false,
+            id: None,
+            parent: None,
+            collapsed: false,
+        }
+    }
+
+    /// Start the timer and open a new session
+    pub fn start(&mut self, clock: &dyn Clock) {
+        if !self.timer.is_running() {
+            self.timer.start(clock);
+            self.sessions.push(Session {
+                start: clock.now(),
+                end: None,
+                switched_from: None,
+            });
+        }
+    }
+
+    /// Stop the timer and close the open session
+    pub fn stop(&mut self, clock: &dyn Clock) {
+        self.timer.stop(clock);
+        let now = clock.now();
+        for session in self.sessions.iter_mut().filter(|s| s.end.is_none()) {
+            session.end = Some(now);
+        }
+    }
+
+    /// Stop the timer as of `at`, during the current run. The rest of the run is dropped.
+    fn stop_at(&mut self, at: SystemTime, clock: &dyn Clock) {
+        self.timer.skip(at, clock.now());
+        self.stop(clock);
+        if let Some(session) = self.sessions.last_mut() {
+            session.end = Some(at.max(session.start));
+        }
+    }
+
+    /// Account for the wall clock having moved back by `skew` while the timer ran
+    pub fn rewind_skew(&mut self, skew: Duration) {
+        self.timer.rewind_skew(skew);
+        for session in self.sessions.iter_mut().filter(|s| s.end.is_none()) {
+            session.start = session.start.checked_sub(skew).unwrap_or(session.start);
+        }
+    }
+
+    /// Leave the time between `from` and `to` out of the running timer, splitting the open
+    /// session around it
+    pub fn skip(&mut self, from: SystemTime, to: SystemTime) {
+        if !self.timer.is_running() {
+            return;
+        }
+        self.timer.skip(from, to);
+        if let Some(index) = self.sessions.iter().position(|s| s.end.is_none()) {
+            if self.sessions[index].start < from {
+                self.sessions[index].end = Some(from);
+                self.sessions.push(Session {
+                    start: to,
+                    end: None,
+                    switched_from: None,
+                });
+            } else {
+                self.sessions[index].start = to;
+            }
+        }
+    }
+
+    /// Record time spent on the task while its timer wasn't running
+    pub fn add_time(&mut self, from: SystemTime, to: SystemTime) {
+        self.timer
+            .add_elapsed(to.duration_since(from).unwrap_or_default());
+        let index = self
+            .sessions
+            .iter()
+            .position(|s| s.start > from)
+            .unwrap_or(self.sessions.len());
+        self.sessions.insert(index, Session {
+            start: from,
+            end: Some(to),
+            switched_from: None,
+        });
+    }
+
+    /// Stop the timer, throwing away the open session and its time
+    pub fn discard_run(&mut self) {
+        self.timer.discard_run();
+        self.sessions.retain(|s| s.end.is_some());
+    }
+
+    /// Whether the task is shown by a filter such as `+work #bug login`: `+` words must match
+    /// the start of the project, `#` words the start of a tag and other words part of the
+    /// description, ignoring case
+    pub fn matches(&self, filter: &str) -> bool {
+        filter.split_whitespace().all(|word| {
+            let word = word.to_lowercase();
+            if let Some(project) = word.strip_prefix('+') {
+                self.project
+                    .as_ref()
+                    .is_some_and(|name| name.to_lowercase().starts_with(project))
+            } else if let Some(tag) = word.strip_prefix('#') {
+                self.tags.iter().any(|name| name.to_lowercase().starts_with(tag))
+            } else {
+                self.description.to_lowercase().contains(&word)
+            }
+        })
+    }
+
+    /// Correct the tracked time by hand, keeping a record of the change
+    pub fn adjust(&mut self, adjustment: Adjustment, clock: &dyn Clock) {
+        let before = self.timer.elapsed(clock);
+        let after = match adjustment {
+            Adjustment::Add(duration) => before + duration,
+            Adjustment::Subtract(duration) => before.saturating_sub(duration),
+            Adjustment::Set(duration) => duration,
+        };
+        self.timer.set_elapsed(after, clock);
+        self.adjustments.push(TimeAdjustment {
+            at: clock.now(),
+            before,
+            after,
+        });
+    }
+
+    /// Zero the timer, discarding the recorded sessions along with the time
+    pub fn reset(&mut self) {
+        self.timer.reset();
+        self.sessions.clear();
+    }
+
+    /// Stop the timer so the task is ready to be archived
+    pub fn complete(&mut self, clock: &dyn Clock) {
+        self.stop(clock);
+    }
+}
+
+/// Audit record of a manual change to a task's tracked time
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct TimeAdjustment {
+    pub at: SystemTime,
+    pub before: Duration,
+    pub after: Duration,
+}
+
+/// A manual correction to a timer, as typed: `+15m`, `-1h30m`, or `=2h` (or just `2h`)
+/// to set the total
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Adjustment {
+    Add(Duration),
+    Subtract(Duration),
+    Set(Duration),
+}
+
+impl std::str::FromStr for Adjustment {
+    type Err = humantime::DurationError;
+
+    fn from_str(input: &str) -> Result<Adjustment, Self::Err> {
+        let input = input.trim();
+        Ok(match input.chars().next() {
+            Some('+') => Adjustment::Add(humantime::parse_duration(input[1..].trim())?),
+            Some('-') => Adjustment::Subtract(humantime::parse_duration(input[1..].trim())?),
+            Some('=') => Adjustment::Set(humantime::parse_duration(input[1..].trim())?),
+            _ => Adjustment::Set(humantime::parse_duration(input)?),
+        })
+    }
+}
+
+/// A single wall-clock interval during which a task's timer was running
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub start: SystemTime,
+    /// `None` while the session is still running
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<SystemTime>,
+    /// Description of the task whose timer was stopped to start this session, in
+    /// exclusive mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub switched_from: Option<String>,
+}
+
+impl Session {
+    /// Start and end of the session, ending `now` if it is still running
+    pub fn interval(&self, now: SystemTime) -> (SystemTime, SystemTime) {
+        (self.start, self.end.unwrap_or(now).max(self.start))
+    }
+}
+
+/// Take `+project` and `#tag` words out of a description, returning what is left of the
+/// description, the project (the last one given) and the tags
+pub fn parse_tags(input: &str) -> (String, Option<String>, Vec<String>) {
+    let mut words = Vec::new();
+    let mut project = None;
+    let mut tags: Vec<String> = Vec::new();
+    for word in input.split_whitespace() {
+        match (word.strip_prefix('+'), word.strip_prefix('#')) {
+            (Some(name), _) if !name.is_empty() => project = Some(name.to_string()),
+            (_, Some(name)) if !name.is_empty() => {
+                if !tags.iter().any(|tag| tag == name) {
+                    tags.push(name.to_string());
+                }
+            }
+            _ => words.push(word),
+        }
+    }
+    (words.join(" "), project, tags)
+}
+
+/// Local midnight at the start of the day `time` falls on
+pub fn start_of_day(time: SystemTime) -> SystemTime {
+    let local = DateTime::<Local>::from(time);
+    time - Duration::new(local.num_seconds_from_midnight() as u64, local.nanosecond())
+}
+
+/// Wall-clock time covered by at least one of `intervals`
+pub fn union_length(mut intervals: Vec<(SystemTime, SystemTime)>) -> Duration {
+    intervals.sort_by_key(|(start, _)| *start);
+    let mut total = Duration::new(0, 0);
+    let mut covered: Option<(SystemTime, SystemTime)> = None;
+    for (start, end) in intervals {
+        covered = match covered {
+            Some((from, to)) if start <= to => Some((from, to.max(end))),
+            Some((from, to)) => {
+                total += to.duration_since(from).unwrap_or_default();
+                Some((start, end))
+            }
+            None => Some((start, end)),
+        };
     }
+    if let Some((from, to)) = covered {
+        total += to.duration_since(from).unwrap_or_default();
+    }
+    total
+}
+
+/// Split a trailing `~<duration>` estimate off a task description.
+/// Input without a parsable estimate is returned untouched.
+pub fn parse_estimate(input: &str) -> (&str, Option<Duration>) {
+    if let Some((description, estimate)) = input.rsplit_once('~') {
+        if let Ok(estimate) = humantime::parse_duration(estimate.trim()) {
+            return (description.trim_end(), Some(estimate));
+        }
+    }
+    (input, None)
 }
 
 impl Default for App {
     fn default() -> App {
         App {
-            input: String::new(),
+            input: LineEditor::default(),
             input_mode: InputMode::Normal,
             tasks: Vec::new(),
             selected_task: 0,
+            filter: String::new(),
+            screen: Screen::Timers,
+            history: History::default(),
+            undo: UndoStack::default(),
+            read_only: false,
+            synced: Cell::new(None),
+            clock: Rc::new(SystemClock),
+            last_seen: None,
+            exclusive: false,
+            daily_goal: None,
+            completed_today: Vec::new(),
+            completed_adjustments: Vec::new(),
+            completed_synced: None,
+            status: None,
+            countdown_end: CountdownEnd::Overtime,
+            countdown_alert: None,
+            break_reminder: None,
+            reminder_dismissed: None,
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// A finished task as archived in `completed_quests`.
+///
+/// Fields added after the first release are all optional so that older files keep loading;
+/// `upgrade` fills in whatever can be recovered from the older formats.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CompletedTask {
+    /// Format version the record was written with, 0 for files from before versioning
+    #[serde(default)]
+    pub version: u32,
+    /// Unique id, also the record's filename in `completed_quests`. Empty until saved.
+    #[serde(default)]
+    pub id: String,
     pub description: String,
     pub total_time: Duration,
-    // completed_date
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<SystemTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<SystemTime>,
+    /// Start of the first session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_started: Option<SystemTime>,
+    #[serde(default)]
+    pub session_count: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sessions: Vec<Session>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub adjustments: Vec<TimeAdjustment>,
 }
 
-impl From<&Task> for CompletedTask {
-    fn from(task: &Task) -> Self {
+impl CompletedTask {
+    /// Current format version of completed task records
+    pub const VERSION: u32 = 3;
+
+    /// Archive record of a task, completed now
+    pub fn new(task: &Task, clock: &dyn Clock) -> CompletedTask {
         CompletedTask {
+            version: CompletedTask::VERSION,
+            id: String::new(),
             description: task.description.clone(),
-            total_time: task.timer.elapsed(),
+            total_time: task.timer.elapsed(clock),
+            completed_at: Some(clock.now()),
+            created_at: task.created_at,
+            first_started: task.sessions.first().map(|session| session.start),
+            session_count: task.sessions.len(),
+            sessions: task.sessions.clone(),
+            estimate: task.estimate,
+            project: task.project.clone(),
+            tags: task.tags.clone(),
+            adjustments: task.adjustments.clone(),
+        }
+    }
+
+    /// Bring a record loaded from an older format up to the current version
+    pub fn upgrade(mut self) -> CompletedTask {
+        if self.version == 0 {
+            // unversioned records may carry sessions but none of the metadata derived from them
+            self.first_started = self.sessions.first().map(|session| session.start);
+            self.session_count = self.sessions.len();
+            self.completed_at = self.sessions.iter().filter_map(|session| session.end).max();
+        }
+        self.version = CompletedTask::VERSION;
+        self
+    }
+}
+
+impl From<CompletedTask> for Task {
+    /// Reopen a completed task, with its timer stopped at the time it was completed with
+    fn from(quest: CompletedTask) -> Self {
+        Task {
+            description: quest.description,
+            timer: crate::timer::Stopwatch::with_elapsed(quest.total_time),
+            sessions: quest.sessions,
+            estimate: quest.estimate,
+            created_at: quest.created_at,
+            project: quest.project,
+            tags: quest.tags,
+            adjustments: quest.adjustments,
+            countdown: false,
+            is_break: false,
+            paused_by_break: false,
+            id: None,
+            parent: None,
+            collapsed: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::storage::tests::TestDir;
+
+    fn app(clock: &FakeClock, tasks: Vec<Task>) -> App {
+        App {
+            tasks,
+            clock: Rc::new(clock.clone()),
+            ..Default::default()
         }
     }
+
+    #[test]
+    fn projects_and_tags_are_taken_out_of_descriptions() {
+        let (description, project, tags) = parse_tags("Fix +web login #bug redirect #bug # +");
+        assert_eq!(description, "Fix login redirect # +");
+        assert_eq!(project.as_deref(), Some("web"));
+        assert_eq!(tags, vec!["bug"]);
+
+        let mut task = Task::new(&description, &FakeClock::new());
+        task.project = project;
+        task.tags = tags;
+        assert!(task.matches("+We #b LOGIN"));
+        assert!(!task.matches("+api"));
+    }
+
+    #[test]
+    fn overlapping_timers_count_towards_the_total_once() {
+        let clock = FakeClock::new();
+        let first = Task::new("first", &clock);
+        clock.advance(Duration::from_secs(600));
+        let mut second = Task::new("second", &clock);
+        clock.advance(Duration::from_secs(600));
+        second.stop(&clock);
+        second.timer.add_elapsed(Duration::from_secs(60));
+
+        let app = app(&clock, vec![first, second]);
+        assert_eq!(app.active_elapsed(), Duration::from_secs(1260));
+    }
+
+    #[test]
+    fn todays_total_includes_quests_completed_today() {
+        let clock = FakeClock::new();
+        let earlier = clock.now() - Duration::from_secs(1200);
+        let mut app = app(&clock, vec![Task::new("running", &clock)]);
+        app.completed_today = vec![(earlier, earlier + Duration::from_secs(600))];
+        clock.advance(Duration::from_secs(300));
+        assert_eq!(app.today_elapsed(), Duration::from_secs(900));
+    }
+
+    #[test]
+    fn todays_total_includes_time_adjusted_today() {
+        let clock = FakeClock::new();
+        let mut yesterday = Task::new("yesterday", &clock);
+        yesterday.adjustments.push(TimeAdjustment {
+            at: clock.now() - Duration::from_secs(24 * 3600),
+            before: Duration::ZERO,
+            after: Duration::from_secs(7200),
+        });
+        let mut app = app(&clock, vec![Task::new("writing", &clock), yesterday]);
+        clock.advance(Duration::from_secs(600));
+        app.tasks[0].adjust(Adjustment::Add(Duration::from_secs(3600)), &clock);
+        assert_eq!(app.today_elapsed(), Duration::from_secs(4200));
+
+        app.completed_adjustments = vec![TimeAdjustment {
+            at: clock.now(),
+            before: Duration::from_secs(1800),
+            after: Duration::from_secs(600),
+        }];
+        assert_eq!(app.today_elapsed(), Duration::from_secs(3000));
+        app.tasks[0].adjust(Adjustment::Set(Duration::ZERO), &clock);
+        assert_eq!(app.today_elapsed(), Duration::ZERO);
+    }
+
+    #[test]
+    fn quests_are_only_reloaded_once_some_are_completed() {
+        let clock = FakeClock::new();
+        let _dir = TestDir::new();
+        let mut app = app(&clock, vec![Task::new("shipped", &clock)]);
+        let earlier = clock.now() - Duration::from_secs(3600);
+        app.completed_today = vec![(earlier, earlier + Duration::from_secs(600))];
+        app.refresh_completed_today().unwrap();
+        assert_eq!(app.completed_today.len(), 1);
+
+        app.complete_selected_task().unwrap();
+        app.refresh_completed_today().unwrap();
+        assert_eq!(app.completed_today, vec![(clock.now(), clock.now())]);
+    }
+
+    #[test]
+    fn starting_a_task_in_exclusive_mode_stops_the_others() {
+        let clock = FakeClock::new();
+        let first = Task::new("first", &clock);
+        clock.advance(Duration::from_secs(600));
+        let mut app = app(&clock, vec![first, Task::new("second", &clock)]);
+        app.exclusive = true;
+        app.switch_to(1);
+        clock.advance(Duration::from_secs(600));
+
+        assert!(!app.tasks[0].timer.is_running());
+        assert_eq!(app.tasks[1].sessions[0].switched_from.as_deref(), Some("first"));
+        assert_eq!(app.active_elapsed(), Duration::from_secs(1200));
+    }
+
+    #[test]
+    fn failures_show_in_the_status_line_for_a_while() {
+        let clock = FakeClock::new();
+        let mut app = app(&clock, vec![Task::new("first", &clock)]);
+        app.read_only = true;
+        let result = app.delete_selected_task();
+        app.report(result);
+        assert_eq!(app.status(), Some("another timeknight is running, this one is read-only"));
+        assert_eq!(app.tasks.len(), 1);
+
+        clock.advance(STATUS_TIMEOUT);
+        assert_eq!(app.status(), None);
+    }
+
+    #[test]
+    fn actions_on_an_empty_list_do_nothing() {
+        let clock = FakeClock::new();
+        let mut app = app(&clock, Vec::new());
+        app.read_only = true;
+        app.move_down();
+        app.move_up();
+        assert!(app.delete_selected_task().is_ok());
+        assert!(app.toggle_play_pause_selected_task().is_ok());
+        assert!(app.complete_selected_task().is_ok());
+        assert!(app.reset_selected_task().is_ok());
+        assert!(app.tasks.is_empty());
+    }
+
+    #[test]
+    fn countdowns_running_out_are_noticed_once_and_can_stop_at_the_estimate() {
+        let clock = FakeClock::new();
+        let start = clock.now();
+        let mut task = Task::new("timeboxed", &clock);
+        task.estimate = Some(Duration::from_secs(600));
+        task.countdown = true;
+        let mut app = app(&clock, vec![task]);
+        // read-only so nothing is paused or saved
+        app.read_only = true;
+
+        clock.advance(Duration::from_secs(300));
+        assert!(!app.end_countdowns(start).unwrap());
+        let since = clock.now();
+        clock.advance(Duration::from_secs(420));
+        assert!(app.end_countdowns(since).unwrap());
+        assert!(!app.end_countdowns(clock.now()).unwrap());
+        assert_eq!(app.countdown(0), Some(Countdown::Overtime(Duration::from_secs(120))));
+
+        let task = &mut app.tasks[0];
+        let runs_out_at = Stopwatch::runs_out_at(&[&task.timer], Duration::from_secs(600)).unwrap();
+        task.stop_at(runs_out_at, &clock);
+        assert!(!task.timer.is_running());
+        assert_eq!(task.sessions[0].end, Some(start + Duration::from_secs(600)));
+        assert_eq!(app.countdown(0), Some(Countdown::Overtime(Duration::ZERO)));
+    }
+
+    #[test]
+    fn break_reminders_come_back_after_being_dismissed() {
+        let clock = FakeClock::new();
+        let work = Task::new("deep work", &clock);
+        let mut pause = Task::new("Break", &clock);
+        pause.is_break = true;
+        let mut app = app(&clock, vec![work, pause]);
+        app.break_reminder = Some(Duration::from_secs(45 * 60));
+
+        clock.advance(Duration::from_secs(30 * 60));
+        assert_eq!(app.break_due(), None);
+        clock.advance(Duration::from_secs(20 * 60));
+        assert_eq!(app.break_due(), Some(Duration::from_secs(50 * 60)));
+        app.dismiss_break_reminder();
+        assert_eq!(app.break_due(), None);
+        clock.advance(Duration::from_secs(45 * 60));
+        assert_eq!(app.break_due(), Some(Duration::from_secs(95 * 60)));
+
+        // the break timer runs alongside but isn't counted as work
+        assert_eq!(app.active_elapsed(), Duration::from_secs(95 * 60));
+        assert_eq!(app.today_elapsed(), Duration::from_secs(95 * 60));
+    }
+
+    #[test]
+    fn breaks_stop_the_running_timers_and_restart_them_even_after_a_reload() {
+        let clock = FakeClock::new();
+        let _dir = TestDir::new();
+        let mut idle = Task::new("idle", &clock);
+        idle.stop(&clock);
+        let mut app = app(&clock, vec![Task::new("deep work", &clock), idle]);
+
+        app.toggle_break().unwrap();
+        assert!(!app.tasks[0].timer.is_running());
+        assert!(app.tasks[2].is_break && app.tasks[2].timer.is_running());
+
+        // a second `timeknight break` only has what was saved to go on
+        let mut app = crate::storage::load_state().unwrap();
+        app.clock = Rc::new(clock.clone());
+        assert!(app.tasks[0].paused_by_break);
+        clock.advance(Duration::from_secs(600));
+        app.toggle_break().unwrap();
+        assert!(app.tasks[0].timer.is_running() && !app.tasks[0].paused_by_break);
+        assert!(!app.tasks[1].timer.is_running());
+        assert!(!app.tasks[2].timer.is_running());
+        assert_eq!(app.tasks[2].timer.elapsed(&clock), Duration::from_secs(600));
+
+        // starting any task ends the break, and the others stay stopped
+        app.toggle_break().unwrap();
+        app.selected_task = 1;
+        app.toggle_play_pause_selected_task().unwrap();
+        assert!(!app.tasks[2].timer.is_running());
+        assert!(!app.tasks[0].timer.is_running() && !app.tasks[0].paused_by_break);
+        assert!(app.tasks[1].timer.is_running());
+    }
+
+    #[test]
+    fn split_tasks_roll_up_their_subtasks_and_fold_them_away() {
+        let clock = FakeClock::new();
+        let mut parent = Task::new("release", &clock);
+        parent.id = Some(1);
+        let mut tests = Task::new("tests", &clock);
+        tests.parent = Some(1);
+        tests.id = Some(2);
+        tests.estimate = Some(Duration::from_secs(1800));
+        let mut flaky = Task::new("flaky test", &clock);
+        flaky.parent = Some(2);
+        flaky.estimate = Some(Duration::from_secs(600));
+        let mut app = app(&clock, vec![parent, tests, flaky, Task::new("email", &clock)]);
+        clock.advance(Duration::from_secs(60));
+
+        assert_eq!(app.depth(2), 2);
+        assert_eq!(app.rollup(0), (Duration::from_secs(180), Some(Duration::from_secs(2400))));
+        assert_eq!(app.rollup(1), (Duration::from_secs(120), Some(Duration::from_secs(2400))));
+
+        app.tasks[0].collapsed = true;
+        assert!(!app.is_listed(1) && !app.is_listed(2));
+        app.move_down();
+        assert_eq!(app.selected_task, 3);
+
+        // removing a task moves its subtasks up a level
+        app.remove_task(1);
+        assert_eq!(app.tasks[1].parent, Some(1));
+        assert_eq!(app.depth(1), 1);
+    }
+
+    #[test]
+    fn splitting_hands_the_timer_and_estimate_to_new_subtasks_after_existing_ones() {
+        let clock = FakeClock::new();
+        let _dir = TestDir::new();
+        let mut release = Task::new("release", &clock);
+        release.project = Some("web".into());
+        release.id = Some(1);
+        release.estimate = Some(Duration::from_secs(7200));
+        let mut notes = Task::new("notes", &clock);
+        notes.stop(&clock);
+        notes.parent = Some(1);
+        let mut email = Task::new("email", &clock);
+        email.stop(&clock);
+        email.estimate = Some(Duration::from_secs(900));
+        let mut app = app(&clock, vec![release, notes, email]);
+        clock.advance(Duration::from_secs(600));
+
+        app.split_selected_task("tests ~30m, fix redirect ~1h,").unwrap();
+        let names: Vec<_> = app.tasks.iter().map(|task| task.description.as_str()).collect();
+        assert_eq!(names, ["release", "notes", "tests", "fix redirect", "email"]);
+        assert_eq!(app.selected_task, 2);
+        assert_eq!(app.depth(3), 1);
+        assert_eq!(app.tasks[3].project.as_deref(), Some("web"));
+
+        // the time so far stays put and the running timer carries on in the first part
+        let release = &app.tasks[0];
+        assert!(!release.timer.is_running());
+        assert_eq!(release.timer.elapsed(&clock), Duration::from_secs(600));
+        assert_eq!(release.estimate, None);
+        assert!(app.tasks[2].timer.is_running());
+        assert!(!app.tasks[3].timer.is_running() && app.tasks[3].sessions.is_empty());
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(app.rollup(0), (Duration::from_secs(660), Some(Duration::from_secs(5400))));
+
+        // a task stopped before splitting keeps its estimate if its parts have none
+        app.selected_task = 4;
+        app.split_selected_task("draft, send").unwrap();
+        assert_eq!(app.tasks[4].estimate, Some(Duration::from_secs(900)));
+        assert_eq!(app.tasks[4].id, Some(2));
+        assert!(app.tasks[5..].iter().all(|task| !task.timer.is_running() && task.parent == Some(2)));
+    }
+
+    #[test]
+    fn split_tasks_count_down_and_pause_across_their_parts() {
+        let clock = FakeClock::new();
+        let _dir = TestDir::new();
+        let mut app = app(&clock, vec![Task::new("release", &clock)]);
+        app.countdown_end = CountdownEnd::Pause;
+        clock.advance(Duration::from_secs(300));
+        app.split_selected_task("tests ~10m, notes ~5m").unwrap();
+        app.selected_task = 0;
+        app.toggle_countdown_selected_task().unwrap();
+        assert_eq!(app.countdown(0), Some(Countdown::Remaining(Duration::from_secs(600))));
+
+        let since = clock.now();
+        clock.advance(Duration::from_secs(540));
+        assert!(!app.end_countdowns(since).unwrap());
+        assert_eq!(app.countdown(0), Some(Countdown::Remaining(Duration::from_secs(60))));
+        clock.advance(Duration::from_secs(120));
+        assert!(app.end_countdowns(since).unwrap());
+        assert!(!app.tasks[1].timer.is_running());
+        assert_eq!(app.tasks[1].timer.elapsed(&clock), Duration::from_secs(600));
+        assert_eq!(app.countdown(0), Some(Countdown::Overtime(Duration::ZERO)));
+    }
+
+    #[test]
+    fn stopping_everything_can_be_undone() {
+        let clock = FakeClock::new();
+        let _dir = TestDir::new();
+        let mut idle = Task::new("idle", &clock);
+        idle.stop(&clock);
+        let mut app = app(&clock, vec![Task::new("writing", &clock), idle, Task::new("music", &clock)]);
+
+        assert_eq!(app.stop_all().unwrap(), vec![0, 2]);
+        assert!(app.tasks.iter().all(|task| !task.timer.is_running()));
+        app.undo().unwrap();
+        assert!(app.tasks[0].timer.is_running() && app.tasks[2].timer.is_running());
+        assert!(!app.tasks[1].timer.is_running());
+    }
+
+    #[test]
+    fn reopening_a_quest_while_read_only_leaves_the_history_alone() {
+        let clock = FakeClock::new();
+        let mut task = Task::new("shipped", &clock);
+        task.complete(&clock);
+        let mut quest = CompletedTask::new(&task, &clock);
+        quest.id = "shipped".into();
+        let mut app = app(&clock, Vec::new());
+        app.history.quests = vec![quest];
+        app.read_only = true;
+
+        assert!(matches!(app.reopen_selected_quest(), Err(crate::Error::ReadOnly)));
+        assert_eq!(app.history.quests.len(), 1);
+        assert!(app.tasks.is_empty());
+    }
+
+    #[test]
+    fn a_quest_that_cant_be_reopened_leaves_nothing_to_undo() {
+        let clock = FakeClock::new();
+        let _dir = TestDir::new();
+        let mut task = Task::new("shipped", &clock);
+        task.complete(&clock);
+        let mut quest = CompletedTask::new(&task, &clock);
+        quest.id = "never-saved".into();
+        let mut app = app(&clock, Vec::new());
+        app.history.quests = vec![quest];
+        app.add_task("draft").unwrap();
+
+        assert!(matches!(app.reopen_selected_quest(), Err(crate::Error::Io(_))));
+        assert_eq!(app.history.quests.len(), 1);
+        // undo takes back the last thing that did happen
+        app.undo().unwrap();
+        assert!(app.tasks.is_empty());
+    }
+
+    #[test]
+    fn restarting_a_countdown_in_overtime_keeps_its_time() {
+        let clock = FakeClock::new();
+        let mut task = Task::new("timeboxed", &clock);
+        task.estimate = Some(Duration::from_secs(600));
+        task.countdown = true;
+        clock.advance(Duration::from_secs(900));
+        task.stop(&clock);
+        let mut app = app(&clock, vec![task]);
+        app.countdown_end = CountdownEnd::Pause;
+
+        let since = clock.now();
+        app.tasks[0].start(&clock);
+        clock.advance(Duration::from_secs(1));
+        assert!(!app.end_countdowns(since).unwrap());
+        assert!(!app.end_countdowns(SystemTime::UNIX_EPOCH).unwrap());
+        assert!(app.tasks[0].timer.is_running());
+        assert_eq!(app.tasks[0].timer.elapsed(&clock), Duration::from_secs(901));
+    }
 }
@@ -1,16 +1,55 @@
+use std::cell::Cell;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Local};
+
+use crate::clock::Clock;
 use crate::state::*;
 
-pub(crate) fn load_state() -> Result<App, crate::Error> {
-    let mut path = dirs::home_dir().expect("could not find $HOME directory");
+/// How many backups of `active.ron` to keep in the `backups` directory
+const MAX_BACKUPS: usize = 20;
+/// Minimum time between two backups, so a busy session doesn't rotate out older ones
+const BACKUP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Data directory chosen in the config file
+static DATA_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Keep everything in `dir` instead of `$HOME/.timeknight`
+pub(crate) fn set_data_dir(dir: PathBuf) {
+    *DATA_DIR.lock().unwrap_or_else(|e| e.into_inner()) = Some(dir);
+}
+
+/// Directory holding the active timers, completed quests and backups
+pub(crate) fn data_dir() -> Result<PathBuf, crate::Error> {
+    if let Some(dir) = DATA_DIR.lock().unwrap_or_else(|e| e.into_inner()).clone() {
+        return Ok(dir);
+    }
+    let mut path = dirs::home_dir().ok_or(crate::Error::MissingHome)?;
     path.push(".timeknight");
-    path.push("active.ron");
+    Ok(path)
+}
+
+pub(crate) fn load_state() -> Result<App, crate::Error> {
+    let dir = data_dir()?;
+    let path = dir.join("active.ron");
     // if no state can be found, create default
 
     if path.exists() {
-        let data = std::fs::read_to_string(path)?;
-        let tasks = ron::from_str(&data)?;
+        let tasks = match read_tasks(&path) {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                error!("could not load {}: {}", path.display(), e);
+                recover_from_backup().ok_or(e)?
+            }
+        };
         Ok(App {
             tasks,
+            undo: load_undo(&dir),
+            synced: Cell::new(modified(&path)),
             ..Default::default()
         })
     } else {
@@ -19,29 +58,413 @@ pub(crate) fn load_state() -> Result<App, crate::Error> {
 }
 
 pub(crate) fn save_state(app: &App) -> Result<(), crate::Error> {
-    let mut path = dirs::home_dir().expect("could not find $HOME directory");
-    path.push(".timeknight");
+    if app.read_only {
+        return Err(crate::Error::ReadOnly);
+    }
 
-    // create `$HOME/.timeknight` dir if missing
+    let mut path = data_dir()?;
+
+    // create the data dir if missing
     std::fs::create_dir_all(&path)?;
 
     path.push("active.ron");
-    std::fs::write(path, ron::ser::to_string_pretty(&app.tasks, ron::ser::PrettyConfig::new())?)?;
+    if let Err(e) = backup_state(&path, app.clock.as_ref()) {
+        warn!("could not back up {}: {}", path.display(), e);
+    }
+    write_atomic(&path, &format_tasks(&app.tasks)?)?;
+    app.synced.set(modified(&path));
+
+    path.set_file_name("undo.ron");
+    write_atomic(&path, &ron::ser::to_string(&app.undo)?)?;
     Ok(())
 }
 
-pub(crate) fn save_completed_task<CT: Into<CompletedTask>>(task: CT) -> Result<(), crate::Error> {
-    let mut path = dirs::home_dir().expect("could not find $HOME directory");
-    path.push(".timeknight");
-    path.push("completed_quests");
+/// Reload tasks and undo history into `app` if `active.ron` was written by someone else
+/// since we last loaded or saved it. Returns whether anything was reloaded.
+pub(crate) fn reload_state(app: &mut App) -> Result<bool, crate::Error> {
+    let dir = data_dir()?;
+    let path = dir.join("active.ron");
+
+    let modified = modified(&path);
+    if modified.is_none() || modified == app.synced.get() {
+        return Ok(false);
+    }
+    app.tasks = read_tasks(&path)?;
+    app.undo = load_undo(&dir);
+    app.synced.set(modified);
+    debug!("reloaded {} after an external change", path.display());
+    Ok(true)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Advisory lock on `$HOME/.timeknight`, held by a running TUI so a second one doesn't
+/// overwrite its timers. Released when dropped.
+pub(crate) struct Lock {
+    path: PathBuf,
+}
+
+pub(crate) enum LockStatus {
+    Acquired(Lock),
+    /// Another live process, with this pid, holds the lock
+    HeldBy(u32),
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!("could not remove lock {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Take the data directory lock, clearing it first if its owner is no longer running
+pub(crate) fn lock() -> Result<LockStatus, crate::Error> {
+    let mut path = data_dir()?;
+    std::fs::create_dir_all(&path)?;
+    path.push("lock");
+
+    for _ in 0..2 {
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                write!(file, "{}", std::process::id())?;
+                return Ok(LockStatus::Acquired(Lock { path }));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                let pid = std::fs::read_to_string(&path)?.trim().parse::<u32>().ok();
+                match pid {
+                    Some(pid) if process_alive(pid) => return Ok(LockStatus::HeldBy(pid)),
+                    _ => {
+                        warn!("removing stale lock {}", path.display());
+                        std::fs::remove_file(&path)?;
+                    }
+                }
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(format!("could not take lock {}", path.display()).into())
+}
+
+/// Whether a process is running. Only answerable where `/proc` exists; elsewhere a lock is
+/// assumed live and has to be removed by hand if its owner crashed.
+fn process_alive(pid: u32) -> bool {
+    let proc = Path::new("/proc");
+    !proc.is_dir() || proc.join(pid.to_string()).exists()
+}
+
+fn read_tasks(path: &Path) -> Result<Vec<Task>, crate::Error> {
+    parse_tasks(&std::fs::read_to_string(path)?)
+}
+
+fn parse_tasks(data: &str) -> Result<Vec<Task>, crate::Error> {
+    Ok(ron::from_str(data)?)
+}
+
+fn format_tasks(tasks: &[Task]) -> Result<String, crate::Error> {
+    Ok(ron::ser::to_string_pretty(tasks, ron::ser::PrettyConfig::new())?)
+}
+
+/// Write `contents` to `path` so that a crash or full disk can never leave a truncated file:
+/// the data goes to a temporary file which is synced to disk and then renamed over `path`.
+fn write_atomic(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let mut file = File::create(&tmp)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&tmp, path)?;
+
+    // sync the directory too, otherwise the rename itself can be lost
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+fn backups_dir() -> Result<PathBuf, crate::Error> {
+    let mut path = data_dir()?;
+    path.push("backups");
+    Ok(path)
+}
+
+/// Backups of `active.ron`, newest first. Their names are timestamps so they sort by age.
+fn list_backups() -> Result<Vec<PathBuf>, crate::Error> {
+    let dir = backups_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut backups = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    backups.retain(|path| path.extension().map(|ext| ext == "ron").unwrap_or(false));
+    backups.sort();
+    backups.reverse();
+    Ok(backups)
+}
+
+/// Copy the current `active.ron` into the backups before it gets replaced, at most once per
+/// `BACKUP_INTERVAL`, and drop the oldest backups beyond `MAX_BACKUPS`.
+fn backup_state(active: &Path, clock: &dyn Clock) -> Result<(), crate::Error> {
+    if !active.exists() {
+        return Ok(());
+    }
+    let backups = list_backups()?;
+    if let Some(newest) = backups.first() {
+        let age = clock
+            .now()
+            .duration_since(std::fs::metadata(newest)?.modified()?)
+            .unwrap_or_default();
+        if age < BACKUP_INTERVAL {
+            return Ok(());
+        }
+    }
+
+    let dir = backups_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let now: DateTime<Local> = clock.now().into();
+    let backup = dir.join(format!("active-{}.ron", now.format("%Y%m%d-%H%M%S")));
+    write_atomic(&backup, &std::fs::read_to_string(active)?)?;
+
+    for old in list_backups()?.iter().skip(MAX_BACKUPS) {
+        std::fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+/// Tasks from the newest backup which still parses
+fn recover_from_backup() -> Option<Vec<Task>> {
+    for backup in list_backups().ok()? {
+        match read_tasks(&backup) {
+            Ok(tasks) => {
+                warn!("recovered active tasks from {}", backup.display());
+                return Some(tasks);
+            }
+            Err(e) => warn!("backup {} is unusable: {}", backup.display(), e),
+        }
+    }
+    None
+}
+
+/// Undo history from the last run. It's only a convenience, so an unreadable file just
+/// starts a fresh history.
+fn load_undo(dir: &Path) -> crate::undo::UndoStack {
+    let path = dir.join("undo.ron");
+
+    match std::fs::read_to_string(&path).map(|data| ron::from_str(&data)) {
+        Ok(Ok(undo)) => undo,
+        Ok(Err(e)) => {
+            warn!("discarding undo history {}: {}", path.display(), e);
+            Default::default()
+        }
+        Err(_) => Default::default(),
+    }
+}
+
+/// Save a completed task as `completed_quests/<id>.ron`, assigning it a fresh id if it
+/// doesn't have one yet. Returns the id the task was saved under.
+pub(crate) fn save_completed_task<CT: Into<CompletedTask>>(task: CT) -> Result<String, crate::Error> {
+    let path = completed_quests_dir()?;
 
     // create `$HOME/.timeknight/completed_quests dir if missing
     std::fs::create_dir_all(&path)?;
 
-    let task = task.into();
+    let mut task = task.into();
+    if task.id.is_empty() {
+        task.id = unique_id(&path, &task);
+    }
+    write_completed_task(&path, &task)?;
+    Ok(task.id)
+}
+
+/// Load every completed task from `$HOME/.timeknight/completed_quests`.
+/// Files which fail to parse are logged and skipped.
+///
+/// Quests saved before ids existed were named after their description, which breaks on
+/// descriptions containing `/` and overwrites duplicates. They're moved to `<id>.ron` as
+/// they are found.
+pub(crate) fn load_completed_tasks() -> Result<Vec<CompletedTask>, crate::Error> {
+    let dir = completed_quests_dir()?;
+
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    // list the directory up front, migrated files are written into it while we go
+    let paths = std::fs::read_dir(&dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut tasks = Vec::new();
+    for path in paths {
+        if !path.is_file() {
+            continue;
+        }
+        let mut task = match ron::from_str::<CompletedTask>(&std::fs::read_to_string(&path)?) {
+            Ok(task) => task.upgrade(),
+            Err(e) => {
+                warn!("skipping {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        if task.id.is_empty() {
+            // the file was last written when the quest was completed
+            if task.completed_at.is_none() {
+                task.completed_at = modified(&path);
+            }
+            task.id = unique_id(&dir, &task);
+            write_completed_task(&dir, &task)?;
+            std::fs::remove_file(&path)?;
+            info!("migrated {} to {}.ron", path.display(), task.id);
+        }
+        tasks.push(task);
+    }
+    Ok(tasks)
+}
+
+/// Remove a completed task's record, e.g. when it is reopened
+pub(crate) fn delete_completed_task(id: &str) -> Result<(), crate::Error> {
+    std::fs::remove_file(completed_quests_dir()?.join(format!("{}.ron", id)))?;
+    Ok(())
+}
+
+/// Modification time of the completed quests directory, which changes whenever a quest is
+/// completed or reopened
+pub(crate) fn completed_modified() -> Option<SystemTime> {
+    modified(&completed_quests_dir().ok()?)
+}
 
-    path.push(&task.description.clone());
-    std::fs::write(path, ron::ser::to_string_pretty(&task, ron::ser::PrettyConfig::new())?)?;
+fn completed_quests_dir() -> Result<PathBuf, crate::Error> {
+    let mut path = data_dir()?;
+    path.push("completed_quests");
+    Ok(path)
+}
+
+fn write_completed_task(dir: &Path, task: &CompletedTask) -> Result<(), crate::Error> {
+    write_atomic(
+        &dir.join(format!("{}.ron", task.id)),
+        &ron::ser::to_string_pretty(task, ron::ser::PrettyConfig::new())?,
+    )?;
     Ok(())
+}
 
+/// A filename-safe id not yet used in `dir`: the completion time followed by a slug of the
+/// description, e.g. `20220301-143000-fix-login-bug`.
+fn unique_id(dir: &Path, task: &CompletedTask) -> String {
+    let completed_at: DateTime<Local> = task.completed_at.unwrap_or(SystemTime::UNIX_EPOCH).into();
+    let mut slug = String::new();
+    for c in task.description.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.chars().count() >= 40 {
+            break;
+        }
+    }
+    let slug = match slug.trim_end_matches('-') {
+        "" => "task",
+        slug => slug,
+    };
+
+    let base = format!("{}-{}", completed_at.format("%Y%m%d-%H%M%S"), slug);
+    let mut id = base.clone();
+    let mut n = 1;
+    while dir.join(format!("{}.ron", id)).exists() {
+        n += 1;
+        id = format!("{}-{}", base, n);
+    }
+    id
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use std::sync::MutexGuard;
+
+    /// Scratch data directory for tests that save, removed again when dropped even if the
+    /// test fails. Tests holding one run one at a time, as there is only one data directory.
+    pub(crate) struct TestDir {
+        path: PathBuf,
+        _serial: MutexGuard<'static, ()>,
+    }
+
+    impl TestDir {
+        pub(crate) fn new() -> TestDir {
+            static SERIAL: Mutex<()> = Mutex::new(());
+            let serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+            let path = std::env::temp_dir().join(format!("timeknight-test-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            set_data_dir(path.clone());
+            TestDir { path, _serial: serial }
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.path);
+            *DATA_DIR.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+    }
+
+    #[test]
+    fn running_tasks_keep_counting_across_a_restart() {
+        let clock = FakeClock::new();
+        let running = Task::new("running", &clock);
+        let mut stopped = Task::new("stopped", &clock);
+        clock.advance(Duration::from_secs(10 * 60));
+        stopped.stop(&clock);
+        clock.advance(Duration::from_secs(5 * 60));
+        let saved = format_tasks(&[running, stopped]).unwrap();
+
+        // timeknight exits and is started again an hour later
+        clock.advance(Duration::from_secs(60 * 60));
+        let tasks = parse_tasks(&saved).unwrap();
+
+        assert!(tasks[0].timer.is_running());
+        assert_eq!(tasks[0].timer.elapsed(&clock), Duration::from_secs(75 * 60));
+        assert!(tasks[0].sessions[0].end.is_none());
+
+        assert!(!tasks[1].timer.is_running());
+        assert_eq!(tasks[1].timer.elapsed(&clock), Duration::from_secs(10 * 60));
+        let session = &tasks[1].sessions[0];
+        assert_eq!(
+            session.end.unwrap().duration_since(session.start).unwrap(),
+            Duration::from_secs(10 * 60)
+        );
+    }
+
+    #[test]
+    fn loads_tasks_saved_before_sessions_and_estimates() {
+        let clock = FakeClock::new();
+        let tasks =
+            parse_tasks(r#"[(description: "old", timer: (elapsed: (secs: 60, nanos: 0)))]"#)
+                .unwrap();
+        assert_eq!(tasks[0].description, "old");
+        assert_eq!(tasks[0].timer.elapsed(&clock), Duration::from_secs(60));
+        assert!(tasks[0].sessions.is_empty());
+        assert!(tasks[0].estimate.is_none());
+    }
+
+    #[test]
+    fn loads_running_timers_in_the_original_format() {
+        let clock = FakeClock::new();
+        let started = clock.now();
+        clock.advance(Duration::from_secs(90));
+        let saved = format!(
+            "[(description: \"old\", timer: (start_time: (secs_since_epoch: {}, nanos_since_epoch: 0), elapsed: (secs: 60, nanos: 0)))]",
+            started.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs()
+        );
+        let tasks = parse_tasks(&saved).unwrap();
+        assert!(tasks[0].timer.is_running());
+        assert_eq!(tasks[0].timer.elapsed(&clock), Duration::from_secs(150));
+    }
 }
@@ -1,48 +1,61 @@
 extern crate num;
 use serde::{Serialize, Deserialize};
 use std::default::Default;
-use std::fmt;
-use std::time::{Duration, Instant};
+use std::time::{Duration, SystemTime};
+
+use crate::clock::Clock;
 
 #[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct Stopwatch {
-    #[serde(with = "optional_instant", default, skip_serializing_if = "Option::is_none")]
-	start_time: Option<Instant>,
+	/// Wall-clock start of the current run. Running time is always measured against the wall
+	/// clock, so a timer keeps counting across restarts, reboots and suspends.
+	#[serde(with = "optional_system_time", default, skip_serializing_if = "Option::is_none")]
+	start_time: Option<SystemTime>,
 	elapsed: Duration,
 }
 
-mod optional_instant {
-    use std::time::{Instant, SystemTime};
-    use serde::{Serialize, Serializer, Deserialize, Deserializer, de::Error};
+/// Writes a bare time rather than `Some(...)`, the format running timers have always been
+/// saved in
+mod optional_system_time {
+    use std::time::SystemTime;
+    use serde::{Serialize, Serializer, Deserialize, Deserializer};
 
-    pub fn serialize<S>(instant: &Option<Instant>, serializer: S) -> Result<S::Ok, S::Error>
+    pub fn serialize<S>(time: &Option<SystemTime>, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        match instant {
-            Some(instant) => {
-                let system_now = SystemTime::now();
-                let instant_now = Instant::now();
-                let approx = system_now - (instant_now - *instant);
-                approx.serialize(serializer)
-            }
+        match time {
+            Some(time) => time.serialize(serializer),
             None => unreachable!()
         }
     }
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Instant>, D::Error>
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<SystemTime>, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let de = SystemTime::deserialize(deserializer)?;
-        let system_now = SystemTime::now();
-        let instant_now = Instant::now();
-        let duration = system_now.duration_since(de).map_err(Error::custom)?;
-        let approx = instant_now - duration;
-        Ok(Some(approx))
+        SystemTime::deserialize(deserializer).map(Some)
     }
 }
-   
+
+/// Where a timer stands against a time budget
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Countdown {
+	/// Time left before the budget is used up
+	Remaining(Duration),
+	/// Time run past the budget
+	Overtime(Duration),
+}
+
+impl Countdown {
+	/// Where `elapsed` stands against `budget`
+	pub fn new(elapsed: Duration, budget: Duration) -> Countdown {
+		match budget.checked_sub(elapsed) {
+			Some(left) if !left.is_zero() => Countdown::Remaining(left),
+			_ => Countdown::Overtime(elapsed - budget),
+		}
+	}
+}
 
 impl Default for Stopwatch {
 	fn default () -> Stopwatch {
@@ -53,29 +66,28 @@ impl Default for Stopwatch {
 	}
 }
 
-impl fmt::Display for Stopwatch {
-	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		return write!(f, "{}ms", self.elapsed_ms());
-	}
-}
-
 impl Stopwatch {
 	pub fn new() -> Stopwatch {
-		let sw: Stopwatch = Default::default();
-		return sw;
+		Default::default()
+	}
+	pub fn with_elapsed(elapsed: Duration) -> Stopwatch {
+		Stopwatch {
+			elapsed,
+			..Default::default()
+		}
 	}
-	pub fn start_new() -> Stopwatch {
+	pub fn start_new(clock: &dyn Clock) -> Stopwatch {
 		let mut sw = Stopwatch::new();
-		sw.start();
-		return sw;
+		sw.start(clock);
+		sw
 	}
 
-	pub fn start(&mut self) {
-		self.start_time = Some(Instant::now());
+	pub fn start(&mut self, clock: &dyn Clock) {
+		self.start_time = Some(clock.now());
 	}
 
-	pub fn stop(&mut self) {
-		self.elapsed = self.elapsed();
+	pub fn stop(&mut self, clock: &dyn Clock) {
+		self.elapsed = self.elapsed(clock);
 		self.start_time = None;
 	}
 
@@ -90,21 +102,235 @@ impl Stopwatch {
 	// }
 
 	pub fn is_running(&self) -> bool {
-		return self.start_time.is_some();
+		self.start_time.is_some()
+	}
+
+	pub fn elapsed(&self, clock: &dyn Clock) -> Duration {
+		self.elapsed + self.current_run(clock).unwrap_or_default()
+	}
+
+	/// How long the timer has been running since it was last started. A start time in the
+	/// future (the clock was set back) counts as no time at all, see `rewind_skew`.
+	pub fn current_run(&self, clock: &dyn Clock) -> Option<Duration> {
+		self.start_time
+			.map(|t1| clock.now().duration_since(t1).unwrap_or_default())
 	}
 
-	pub fn elapsed(&self) -> Duration {
-		match self.start_time {
-			Some(t1) => {
-				return t1.elapsed() + self.elapsed;
-			},
-			None => {
-				return self.elapsed;
-			},
+	/// Wall-clock time the current run started
+	pub fn start_time(&self) -> Option<SystemTime> {
+		self.start_time
+	}
+
+	/// Move the start of the current run back by `skew`, after the wall clock jumped backwards
+	/// by that much, so the time already run is neither lost nor counted twice.
+	pub fn rewind_skew(&mut self, skew: Duration) {
+		self.start_time = self.start_time.and_then(|t1| t1.checked_sub(skew));
+	}
+
+	/// Leave the time between `from` and `to` out of the current run, e.g. time spent away
+	/// from the desk. The run carries on from `to`.
+	pub fn skip(&mut self, from: SystemTime, to: SystemTime) {
+		if let Some(t1) = self.start_time {
+			if t1 < to {
+				self.elapsed += from.duration_since(t1).unwrap_or_default();
+				self.start_time = Some(to);
+			}
+		}
+	}
+
+	/// Make the total elapsed time `elapsed`. A running timer carries on counting from there.
+	pub fn set_elapsed(&mut self, elapsed: Duration, clock: &dyn Clock) {
+		if self.is_running() {
+			self.start_time = Some(clock.now());
+		}
+		self.elapsed = elapsed;
+	}
+
+	/// Count extra time, as if the timer had been running for it
+	pub fn add_elapsed(&mut self, duration: Duration) {
+		self.elapsed += duration;
+	}
+
+	/// Wall-clock time at which `timers` between them reach `budget`, `None` if none is
+	/// running or they had already used the budget up before the first current run
+	pub fn runs_out_at(timers: &[&Stopwatch], budget: Duration) -> Option<SystemTime> {
+		let mut starts: Vec<SystemTime> = timers.iter().filter_map(|sw| sw.start_time).collect();
+		starts.sort();
+		let mut total: Duration = timers.iter().map(|sw| sw.elapsed).sum();
+		// the total grows faster with each timer that starts
+		for (i, &start) in starts.iter().enumerate() {
+			let left = budget.checked_sub(total).filter(|left| !left.is_zero())?;
+			let running = i as u32 + 1;
+			match starts.get(i + 1) {
+				Some(&next) if next.duration_since(start).unwrap_or_default() * running < left => {
+					total += next.duration_since(start).unwrap_or_default() * running;
+				}
+				_ => return Some(start + left / running),
+			}
 		}
+		None
 	}
-	pub fn elapsed_ms(&self) -> i64 {
-		let dur = self.elapsed();
-		return (dur.as_secs() * 1000 + (dur.subsec_nanos() / 1000000) as u64) as i64;
+
+	/// Stop the timer, throwing away the current run
+	pub fn discard_run(&mut self) {
+		self.start_time = None;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::clock::FakeClock;
+
+	#[test]
+	fn counts_only_while_running() {
+		let clock = FakeClock::new();
+		let mut sw = Stopwatch::new();
+		clock.advance(Duration::from_secs(10));
+		assert_eq!(sw.elapsed(&clock), Duration::ZERO);
+
+		sw.start(&clock);
+		clock.advance(Duration::from_secs(30));
+		assert!(sw.is_running());
+		assert_eq!(sw.elapsed(&clock), Duration::from_secs(30));
+
+		sw.stop(&clock);
+		clock.advance(Duration::from_secs(60));
+		assert!(!sw.is_running());
+		assert_eq!(sw.elapsed(&clock), Duration::from_secs(30));
+
+		sw.start(&clock);
+		clock.advance(Duration::from_secs(5));
+		assert_eq!(sw.elapsed(&clock), Duration::from_secs(35));
+	}
+
+	#[test]
+	fn reset_stops_and_zeroes() {
+		let clock = FakeClock::new();
+		let mut sw = Stopwatch::start_new(&clock);
+		clock.advance(Duration::from_secs(90));
+		sw.reset();
+		clock.advance(Duration::from_secs(90));
+		assert!(!sw.is_running());
+		assert_eq!(sw.elapsed(&clock), Duration::ZERO);
+	}
+
+	#[test]
+	fn running_timer_keeps_counting_after_round_trip() {
+		let clock = FakeClock::new();
+		let mut sw = Stopwatch::with_elapsed(Duration::from_secs(100));
+		sw.start(&clock);
+		clock.advance(Duration::from_secs(20));
+
+		let saved = ron::to_string(&sw).unwrap();
+		clock.advance(Duration::from_secs(40));
+		let loaded: Stopwatch = ron::from_str(&saved).unwrap();
+
+		assert!(loaded.is_running());
+		assert_eq!(loaded.elapsed(&clock), Duration::from_secs(160));
+	}
+
+	#[test]
+	fn clock_set_back_before_the_start_counts_no_time() {
+		let clock = FakeClock::new();
+		let sw = Stopwatch::start_new(&clock);
+		clock.rewind(Duration::from_secs(60));
+		assert!(sw.is_running());
+		assert_eq!(sw.elapsed(&clock), Duration::ZERO);
+	}
+
+	#[test]
+	fn rewinding_skew_keeps_time_already_run() {
+		let clock = FakeClock::new();
+		let mut sw = Stopwatch::start_new(&clock);
+		clock.advance(Duration::from_secs(600));
+		clock.rewind(Duration::from_secs(3600));
+		sw.rewind_skew(Duration::from_secs(3600));
+		assert_eq!(sw.elapsed(&clock), Duration::from_secs(600));
+	}
+
+	#[test]
+	fn discarding_a_run_keeps_earlier_time() {
+		let clock = FakeClock::new();
+		let mut sw = Stopwatch::with_elapsed(Duration::from_secs(60));
+		sw.start(&clock);
+		clock.advance(Duration::from_secs(12 * 3600));
+		sw.discard_run();
+		assert!(!sw.is_running());
+		assert_eq!(sw.elapsed(&clock), Duration::from_secs(60));
+	}
+
+	#[test]
+	fn skipping_away_time_leaves_it_out() {
+		let clock = FakeClock::new();
+		let mut sw = Stopwatch::start_new(&clock);
+		clock.advance(Duration::from_secs(600));
+		let away = clock.now();
+		clock.advance(Duration::from_secs(3600));
+		sw.skip(away, clock.now());
+		clock.advance(Duration::from_secs(60));
+		assert!(sw.is_running());
+		assert_eq!(sw.elapsed(&clock), Duration::from_secs(660));
+	}
+
+	#[test]
+	fn setting_elapsed_on_a_running_timer_keeps_counting_from_there() {
+		let clock = FakeClock::new();
+		let mut sw = Stopwatch::start_new(&clock);
+		clock.advance(Duration::from_secs(600));
+		sw.set_elapsed(Duration::from_secs(60), &clock);
+		clock.advance(Duration::from_secs(30));
+		assert!(sw.is_running());
+		assert_eq!(sw.elapsed(&clock), Duration::from_secs(90));
+	}
+
+	#[test]
+	fn stopped_timer_round_trips_unchanged() {
+		let clock = FakeClock::new();
+		let mut sw = Stopwatch::start_new(&clock);
+		clock.advance(Duration::from_secs(20));
+		sw.stop(&clock);
+
+		let loaded: Stopwatch = ron::from_str(&ron::to_string(&sw).unwrap()).unwrap();
+		clock.advance(Duration::from_secs(40));
+		assert!(!loaded.is_running());
+		assert_eq!(loaded.elapsed(&clock), Duration::from_secs(20));
+	}
+
+	#[test]
+	fn counts_down_from_a_budget_into_overtime() {
+		let clock = FakeClock::new();
+		let start = clock.now();
+		let budget = Duration::from_secs(600);
+		let mut sw = Stopwatch::with_elapsed(Duration::from_secs(240));
+		sw.start(&clock);
+		assert_eq!(Stopwatch::runs_out_at(&[&sw], budget), Some(start + Duration::from_secs(360)));
+
+		clock.advance(Duration::from_secs(60));
+		assert_eq!(Countdown::new(sw.elapsed(&clock), budget), Countdown::Remaining(Duration::from_secs(300)));
+		clock.advance(Duration::from_secs(330));
+		assert_eq!(Countdown::new(sw.elapsed(&clock), budget), Countdown::Overtime(Duration::from_secs(30)));
+
+		// restarting once past the budget doesn't run out again
+		sw.stop(&clock);
+		sw.start(&clock);
+		assert_eq!(Stopwatch::runs_out_at(&[&sw], budget), None);
+	}
+
+	#[test]
+	fn timers_running_side_by_side_run_out_sooner() {
+		let clock = FakeClock::new();
+		let start = clock.now();
+		let budget = Duration::from_secs(600);
+		let first = Stopwatch::start_new(&clock);
+		clock.advance(Duration::from_secs(100));
+		let mut second = Stopwatch::with_elapsed(Duration::from_secs(100));
+		second.start(&clock);
+		let stopped = Stopwatch::with_elapsed(Duration::from_secs(200));
+
+		// 300s counted before, 100s by the first alone, then the last 200s go twice as fast
+		let at = Stopwatch::runs_out_at(&[&first, &second, &stopped], budget);
+		assert_eq!(at, Some(start + Duration::from_secs(200)));
+		assert_eq!(Stopwatch::runs_out_at(&[&stopped], budget), None);
 	}
 }
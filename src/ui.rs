@@ -1,119 +1,411 @@
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, Event, KeyCode,
+        KeyModifiers,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use std::time::Duration;
-use std::{error::Error, io};
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime};
 use tui::{
     backend::{Backend, CrosstermBackend},
     layout::{Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Span, Spans, Text},
-    widgets::{Block, Borders, List, ListItem, Paragraph},
+    widgets::{Block, Borders, Gauge, List, ListItem, ListState, Paragraph, Tabs},
     Frame, Terminal,
 };
+use chrono::{DateTime, Local};
 use unicode_width::UnicodeWidthStr;
 
+use crate::config::{Action, Config, Keymap, Theme};
 use crate::state::*;
+use crate::timer::Countdown;
+
+/// A gap this long between event loop ticks means the machine was suspended
+const SUSPEND_GAP: Duration = Duration::from_secs(2 * 60);
+
+/// How long tasks whose countdown ran out keep flashing
+const COUNTDOWN_FLASH: Duration = Duration::from_secs(3);
+
+/// Answers to the away and long-run prompts. These are fixed keys rather than part of the
+/// keymap: nothing else can be done while a prompt is open, so they can't clash.
+const KEEP: KeyCode = KeyCode::Char('k');
+const DISCARD: KeyCode = KeyCode::Char('d');
+const REASSIGN: KeyCode = KeyCode::Char('r');
+
+pub fn run(mut app: App, config: Config) -> crate::Result<()> {
+    // only one instance may write at a time, any others just watch
+    let lock = match crate::storage::lock()? {
+        crate::storage::LockStatus::Acquired(lock) => Some(lock),
+        crate::storage::LockStatus::HeldBy(pid) => {
+            warn!("timeknight is already running (pid {}), opening read-only", pid);
+            app.read_only = true;
+            None
+        }
+    };
+
+    // put the terminal back before a panic message is printed, otherwise it goes to the
+    // alternate screen and the shell is left in raw mode
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+    }));
 
-pub fn run(app: App) -> Result<(), Box<dyn Error>> {
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
     // create app and run it
     // let app = App::default();
-    let res = run_app(&mut terminal, app);
+    let res = run_app(&mut terminal, app, lock, &config);
 
     // restore terminal
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
     terminal.show_cursor()?;
 
-    if let Err(err) = res {
-        println!("{:?}", err)
-    }
+    Ok(res?)
+}
 
-    Ok(())
+/// Best effort terminal cleanup for the panic hook, where errors can't go anywhere
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(
+        io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableBracketedPaste,
+        crossterm::cursor::Show
+    );
 }
 
-fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<()> {
+fn run_app<B: Backend>(
+    terminal: &mut Terminal<B>,
+    mut app: App,
+    mut lock: Option<crate::storage::Lock>,
+    config: &Config,
+) -> io::Result<()> {
+    if let Some(skew) = app.check_clock() {
+        let result = app.save();
+        app.report(result);
+        prompt(terminal, " Clock Changed ", &format!(
+            "The system clock is {} behind the start of a running timer.\n\
+             Its run has been restarted from now.\n\nPress Enter to continue.",
+            humantime::format_duration(Duration::new(skew.as_secs(), 0))
+        ), &[KeyCode::Enter])?;
+    }
+    if !app.read_only {
+        confirm_long_runs(terminal, &mut app, config)?;
+    }
+    // countdowns which ran out while timeknight wasn't open
+    end_countdowns(&mut app, SystemTime::UNIX_EPOCH)?;
+
+    let mut last_tick = app.clock.now();
+    let mut last_input = last_tick;
     loop {
-        terminal.draw(|f| ui(f, &mut app))?;
-        if crossterm::event::poll(Duration::from_millis(500))? {
-            if let Event::Key(key) = event::read()? {
+        // a read-only instance takes over once the writing one exits
+        if lock.is_none() {
+            if let Ok(crate::storage::LockStatus::Acquired(acquired)) = crate::storage::lock() {
+                lock = Some(acquired);
+                app.read_only = false;
+            }
+        }
+        let reloaded = app.reload_if_changed();
+        app.report(reloaded);
+        let refreshed = app.refresh_completed_today();
+        app.report(refreshed);
+        if app.check_clock().is_some() {
+            let result = app.save();
+            app.report(result);
+        }
+
+        // ticks are half a second apart, a long gap means the machine was asleep
+        let now = app.clock.now();
+        let suspended = now.duration_since(last_tick).unwrap_or_default() > SUSPEND_GAP;
+        let idle_for = now.duration_since(last_input).unwrap_or_default();
+        let away_since = match suspended {
+            true => Some(last_tick),
+            false if !config.idle.is_zero() && idle_for > config.idle => Some(last_input),
+            false => None,
+        };
+        if let Some(since) = away_since {
+            // time away during a break is still break time
+            if !app.read_only && app.tasks.iter().any(|task| task.timer.is_running() && !task.is_break) {
+                terminal.draw(|f| ui(f, &mut app, config))?;
+                resolve_away_time(terminal, &mut app, since, config)?;
+            }
+            last_input = app.clock.now();
+        }
+        end_countdowns(&mut app, last_tick)?;
+        last_tick = app.clock.now();
+
+        terminal.draw(|f| ui(f, &mut app, config))?;
+        if crossterm::event::poll(config.poll_rate)? {
+            let event = event::read()?;
+            if let Event::Paste(text) = &event {
+                last_input = app.clock.now();
+                paste(&mut app, text);
+            }
+            if let Event::Key(key) = event {
+                last_input = app.clock.now();
                 match app.input_mode {
-                    InputMode::Normal => match key.code {
-                        KeyCode::Char('a') => {
-                            app.input_mode = InputMode::Editing;
+                    InputMode::Normal if app.screen == Screen::History => match config.keys.action(&key) {
+                        Some(Action::History) => {
+                            app.screen = Screen::Timers;
                         }
-
-                        KeyCode::Char('q') => {
+                        Some(Action::Quit) => {
                             return Ok(());
                         }
+                        Some(Action::Up) => {
+                            app.history.move_up();
+                        }
+                        Some(Action::Down) => {
+                            app.history.move_down();
+                        }
+                        Some(Action::Filter) => {
+                            app.input_mode = InputMode::Searching;
+                        }
+                        Some(Action::Reopen) => {
+                            let result = app.reopen_selected_quest();
+                            app.report(result);
+                        }
+                        _ if key.code == KeyCode::Esc => {
+                            app.history.query.clear();
+                            app.history.move_up_if_past_end();
+                        }
+                        _ => {}
+                    },
+                    InputMode::Normal => match config.keys.action(&key) {
+                        // keys acting on the selected task do nothing while the filter hides it
+                        Some(action) if action.needs_selection() && !app.selection_visible() => {}
 
-                        KeyCode::Char('k') => {
+                        Some(Action::History) => {
+                            let result = app.show_history();
+                            app.report(result);
+                        }
+                        Some(Action::Add) => {
+                            app.start_adding();
+                        }
+                        Some(Action::Filter) => {
+                            app.input_mode = InputMode::Searching;
+                        }
+                        Some(Action::Adjust) => {
+                            app.input_mode = InputMode::Adjusting;
+                        }
+                        Some(Action::Countdown) => {
+                            let result = app.toggle_countdown_selected_task();
+                            app.report(result);
+                        }
+                        Some(Action::Break) => {
+                            let result = app.toggle_break();
+                            app.report(result);
+                        }
+                        Some(Action::Split) => {
+                            app.input_mode = InputMode::Splitting;
+                        }
+                        Some(Action::Fold) => {
+                            let result = app.toggle_fold_selected_task();
+                            app.report(result);
+                        }
+                        Some(Action::Edit) => {
+                            app.start_renaming();
+                        }
+                        Some(Action::Quit) => {
+                            return Ok(());
+                        }
+                        Some(Action::Up) => {
                             app.move_up();
                         }
-
-                        KeyCode::Char('j') => {
+                        Some(Action::Down) => {
                             app.move_down();
                         }
-
-                        KeyCode::Char('l') => {
-                            let _ = app.toggle_play_pause_selected_task();
+                        Some(Action::Toggle) => {
+                            let result = app.toggle_play_pause_selected_task();
+                            app.report(result);
                         }
-
-                        KeyCode::Char('x') => {
-                            let _ = app.delete_selected_task();
+                        Some(Action::Delete) => {
+                            let result = app.delete_selected_task();
+                            app.report(result);
                         }
-
-                        KeyCode::Char('r') => {
-                            let _ = app.reset_selected_task();
+                        Some(Action::Redo) => {
+                            let result = app.redo();
+                            app.report(result);
+                        }
+                        Some(Action::Reset) => {
+                            let result = app.reset_selected_task();
+                            app.report(result);
                         }
-                        KeyCode::Char('C') => {
-                            let _ = app.complete_selected_task();
+                        Some(Action::Undo) => {
+                            let result = app.undo();
+                            app.report(result);
                         }
-                        KeyCode::Char('s') => {
-                            let _ = app.save();
+                        Some(Action::Complete) => {
+                            let result = app.complete_selected_task();
+                            app.report(result);
                         }
-                        
-                        KeyCode::Char('?') => {
+                        Some(Action::Save) => {
+                            let result = app.save();
+                            app.report(result);
+                        }
+                        Some(Action::Report) => {
+                            let report = crate::storage::load_completed_tasks()
+                                .map(|tasks| crate::report::Report::new(&tasks).to_string())
+                                .unwrap_or_else(|e| format!("could not load completed quests: {}", e));
+                            terminal.draw(|f| draw_report(f, &report, &config.theme))?;
+
+                            loop {
+                                if let Event::Key(key) = event::read()? {
+                                    if key.code == KeyCode::Esc
+                                        || matches!(config.keys.action(&key), Some(Action::Report | Action::Quit))
+                                    {
+                                        break;
+                                    }
+                                }
+                            }
+                            resume_after_popup(&mut app, &mut last_tick, &mut last_input)?;
+                        }
+                        Some(Action::Help) => {
                             // mini event loop just for the popup
-                            terminal.draw(|f| draw_popup(f))?;
+                            terminal.draw(|f| draw_popup(f, config))?;
 
                             loop {
                                 if let Event::Key(key) = event::read()? {
-                                    if  key.code == KeyCode::Esc ||
-                                        key.code == KeyCode::Char('?') ||
-                                        key.code == KeyCode::Char('q') {
+                                    if key.code == KeyCode::Esc
+                                        || matches!(config.keys.action(&key), Some(Action::Help | Action::Quit))
+                                    {
                                         break;
                                     }
                                 }
                             }
+                            resume_after_popup(&mut app, &mut last_tick, &mut last_input)?;
+                        }
+                        _ if key.code == KeyCode::Esc && app.break_due().is_some() => {
+                            app.dismiss_break_reminder();
+                        }
+                        _ if key.code == KeyCode::Esc => {
+                            app.filter.clear();
                         }
                         _ => {}
                     },
-                    InputMode::Editing => match key.code {
+                    InputMode::Editing | InputMode::Adjusting | InputMode::Renaming | InputMode::Splitting => match key.code {
                         KeyCode::Enter => {
-                            let description: String = app.input.drain(..).collect();
-                            let _ = app.add_task(&description);
+                            let input = app.input.take();
+                            match app.input_mode {
+                                InputMode::Adjusting => {
+                                    app.input_mode = InputMode::Normal;
+                                    let result = app.adjust_selected_task(&input);
+                                    app.report(result);
+                                }
+                                InputMode::Renaming => {
+                                    app.input_mode = InputMode::Normal;
+                                    let result = app.rename_selected_task(&input);
+                                    app.report(result);
+                                }
+                                InputMode::Splitting => {
+                                    app.input_mode = InputMode::Normal;
+                                    let result = app.split_selected_task(&input);
+                                    app.report(result);
+                                }
+                                _ => {
+                                    let result = app.add_task(&input);
+                                    app.report(result);
+                                }
+                            }
                         }
+                        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => match c {
+                            'w' => app.input.delete_word(),
+                            'u' => app.input.delete_to_start(),
+                            'k' => app.input.delete_to_end(),
+                            'a' => app.input.home(),
+                            'e' => app.input.end(),
+                            // a "\n" pasted where bracketed paste isn't supported, which raw
+                            // mode reports as ctrl-j
+                            'j' => app.input.insert(' '),
+                            _ => {}
+                        },
                         KeyCode::Char(c) => {
-                            app.input.push(c);
+                            app.input.insert(c);
                         }
                         KeyCode::Backspace => {
-                            app.input.pop();
+                            app.input.backspace();
+                        }
+                        KeyCode::Delete => {
+                            app.input.delete();
+                        }
+                        KeyCode::Left => {
+                            app.input.left();
+                        }
+                        KeyCode::Right if app.input.completion().is_some() => {
+                            app.input.complete();
+                        }
+                        KeyCode::Right => {
+                            app.input.right();
+                        }
+                        KeyCode::Home => {
+                            app.input.home();
+                        }
+                        KeyCode::End => {
+                            app.input.end();
+                        }
+                        KeyCode::Up => {
+                            app.input.previous();
+                        }
+                        KeyCode::Down => {
+                            app.input.next();
+                        }
+                        KeyCode::Tab => {
+                            app.input.complete();
+                        }
+                        KeyCode::Esc => {
+                            app.input.take();
+                            app.input_mode = InputMode::Normal;
+                        }
+                        _ => {}
+                    },
+                    InputMode::Searching if app.screen == Screen::Timers => match key.code {
+                        KeyCode::Enter => {
+                            app.input_mode = InputMode::Normal;
+                        }
+                        KeyCode::Char(c) => {
+                            app.filter.push(c);
+                            app.select_visible();
+                        }
+                        KeyCode::Backspace => {
+                            app.filter.pop();
+                            app.select_visible();
                         }
                         KeyCode::Esc => {
+                            app.filter.clear();
+                            app.input_mode = InputMode::Normal;
+                        }
+                        _ => {}
+                    },
+                    InputMode::Searching => match key.code {
+                        KeyCode::Enter => {
+                            app.input_mode = InputMode::Normal;
+                        }
+                        KeyCode::Char(c) => {
+                            app.history.query.push(c);
+                            app.history.selected = 0;
+                        }
+                        KeyCode::Backspace => {
+                            app.history.query.pop();
+                            app.history.selected = 0;
+                        }
+                        KeyCode::Esc => {
+                            app.history.query.clear();
+                            app.history.move_up_if_past_end();
                             app.input_mode = InputMode::Normal;
                         }
                         _ => {}
@@ -125,11 +417,179 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<(
     }
 }
 
-fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
-    let task_running_color = Color::Rgb(255, 0, 200);
-    let title_text = " ♞ TimeKnight ";
-    let total_time = humantime::format_duration(Duration::new(app.active_elapsed().as_secs(), 0));
-    let time_text = format!(" Total Time: {} ", total_time);
+/// Put pasted text into whatever is being typed, with line breaks turned into spaces so
+/// a pasted line doesn't submit the input
+fn paste(app: &mut App, text: &str) {
+    let text = text.replace("\r\n", " ").replace(['\r', '\n'], " ");
+    match app.input_mode {
+        InputMode::Editing | InputMode::Adjusting | InputMode::Renaming | InputMode::Splitting => text.chars().for_each(|c| app.input.insert(c)),
+        InputMode::Searching if app.screen == Screen::Timers => {
+            app.filter.push_str(&text);
+            app.select_visible();
+        }
+        InputMode::Searching => {
+            app.history.query.push_str(&text);
+            app.history.selected = 0;
+        }
+        InputMode::Normal => {}
+    }
+}
+
+/// Handle the countdowns which ran out after `since`, ringing the terminal bell if any did
+fn end_countdowns(app: &mut App, since: SystemTime) -> io::Result<()> {
+    let ended = app.end_countdowns(since);
+    if let Ok(true) = ended {
+        let mut stdout = io::stdout();
+        stdout.write_all(b"\x07")?;
+        stdout.flush()?;
+    }
+    app.report(ended);
+    Ok(())
+}
+
+/// Catch up after a popup held up the loop: countdowns which ran out meanwhile are handled,
+/// and the time it was open isn't taken for the machine sleeping or the user being away
+fn resume_after_popup(app: &mut App, last_tick: &mut SystemTime, last_input: &mut SystemTime) -> io::Result<()> {
+    end_countdowns(app, *last_tick)?;
+    *last_tick = app.clock.now();
+    *last_input = *last_tick;
+    Ok(())
+}
+
+/// Ask about every timer which has been running for longer than `config.long_run`, e.g. one
+/// left on overnight, offering to keep or throw away that run
+fn confirm_long_runs<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    config: &Config,
+) -> io::Result<()> {
+    for index in 0..app.tasks.len() {
+        let task = &app.tasks[index];
+        let run = match task.timer.current_run(app.clock.as_ref()) {
+            Some(run) if run > config.long_run => run,
+            _ => continue,
+        };
+        let started = task
+            .timer
+            .start_time()
+            .map(|start| DateTime::<Local>::from(start).format("%a %H:%M").to_string())
+            .unwrap_or_default();
+        let text = format!(
+            "'{}' has been running for {} (since {}).\n\
+             Was it left on by accident?\n\n\
+             k: keep the time    d: discard this run and stop the timer",
+            task.description,
+            humantime::format_duration(Duration::new(run.as_secs(), 0)),
+            started
+        );
+        if prompt(terminal, " Still Working? ", &text, &[KEEP, DISCARD])? == DISCARD {
+            let result = app.discard_current_run(index);
+            app.report(result);
+        }
+    }
+    Ok(())
+}
+
+/// Ask what to do with the time since `since` on the running timers, after the machine was
+/// asleep or nobody touched the keyboard for a while. The away time ends when this is answered.
+fn resolve_away_time<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    since: std::time::SystemTime,
+    config: &Config,
+) -> io::Result<()> {
+    let running: Vec<&str> = app
+        .tasks
+        .iter()
+        .filter(|task| task.timer.is_running() && !task.is_break)
+        .map(|task| task.description.as_str())
+        .collect();
+    let text = format!(
+        "You've been away since {}, with '{}' running.\n\
+         What should happen to that time?\n\n\
+         k: keep it    d: discard it    r: reassign it to another task",
+        DateTime::<Local>::from(since).format("%H:%M"),
+        running.join("', '")
+    );
+    let choice = prompt(terminal, " Welcome Back ", &text, &[KEEP, DISCARD, REASSIGN])?;
+    let until = app.clock.now();
+    match choice {
+        DISCARD => {
+            let result = app.discard_away_time(since, until);
+            app.report(result);
+        }
+        REASSIGN => {
+            if let Some(index) = pick_task(terminal, app, " Reassign Away Time To ", config)? {
+                let result = app.reassign_away_time(since, until, index);
+                app.report(result);
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Let the user choose one of the active tasks with the up/down keys and Enter, or cancel
+/// with Esc
+fn pick_task<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &App,
+    title: &str,
+    config: &Config,
+) -> io::Result<Option<usize>> {
+    let mut selected = app.selected_task;
+    loop {
+        terminal.draw(|f| draw_picker(f, title, app, selected))?;
+        if let Event::Key(key) = event::read()? {
+            match (key.code, config.keys.action(&key)) {
+                (KeyCode::Enter, _) => return Ok(Some(selected)),
+                (KeyCode::Esc, _) => return Ok(None),
+                (KeyCode::Up, _) | (_, Some(Action::Up)) => selected = selected.saturating_sub(1),
+                (KeyCode::Down, _) | (_, Some(Action::Down)) => {
+                    selected = (selected + 1).min(app.tasks.len().saturating_sub(1))
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Show `text` in a popup until one of `keys` is pressed, returning that key. Esc picks the
+/// first key.
+fn prompt<B: Backend>(
+    terminal: &mut Terminal<B>,
+    title: &str,
+    text: &str,
+    keys: &[KeyCode],
+) -> io::Result<KeyCode> {
+    terminal.draw(|f| draw_prompt(f, title, text))?;
+    loop {
+        if let Event::Key(key) = event::read()? {
+            if keys.contains(&key.code) {
+                return Ok(key.code);
+            }
+            if key.code == KeyCode::Esc {
+                return Ok(keys[0]);
+            }
+        }
+    }
+}
+
+fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App, config: &Config) {
+    let theme = &config.theme;
+    let title_text = match app.read_only {
+        true => " ♞ TimeKnight (read-only) ",
+        false => " ♞ TimeKnight ",
+    };
+    let today = app.today_elapsed();
+    let time_text = format!(
+        " Today: {} ",
+        humantime::format_duration(Duration::new(today.as_secs(), 0))
+    );
+    let goal_width = match app.daily_goal {
+        Some(goal) if !goal.is_zero() => 24,
+        _ => 0,
+    };
 
     let vertical_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -147,6 +607,7 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         .constraints([
             Constraint::Length(title_text.len() as u16),
             Constraint::Min(1),
+            Constraint::Length(goal_width),
             Constraint::Length(time_text.len() as u16),
         ].as_ref())
         .split(tui::layout::Rect {
@@ -161,50 +622,131 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         Span::styled(title_text,
             Style::default()
                 .fg(Color::Black)
-                .bg(Color::Blue)
+                .bg(theme.title)
                 .add_modifier(Modifier::BOLD))
-    ).style(Style::default().bg(Color::Rgb(20,20,20)));
+    ).style(Style::default().bg(theme.header));
     f.render_widget(titlebar, header_layout[0]);
 
-    // Title Spacer
-    let spacer = Block::default()
+    // Screen Tabs
+    let tabs = Tabs::new(vec![Spans::from(" Timers "), Spans::from(" History ")])
+        .select(match app.screen {
+            Screen::Timers => 0,
+            Screen::History => 1,
+        })
+        .divider("")
+        .highlight_style(Style::default()
+            .fg(Color::White)
+            .add_modifier(Modifier::BOLD))
         .style(Style::default()
-            .bg(Color::Rgb(20,20,20)));
-    f.render_widget(spacer, header_layout[1]);
+            .fg(Color::DarkGray)
+            .bg(theme.header));
+    f.render_widget(tabs, header_layout[1]);
+
+    // Daily Goal
+    if let Some(goal) = app.daily_goal.filter(|goal| !goal.is_zero()) {
+        let ratio = today.as_secs_f64() / goal.as_secs_f64();
+        let gauge = Gauge::default()
+            .ratio(ratio.min(1.0))
+            .label(format!("{:.0}% of {}", ratio * 100.0, humantime::format_duration(goal)))
+            .gauge_style(Style::default()
+                .fg(if ratio >= 1.0 { theme.on_track } else { theme.running })
+                .bg(theme.header));
+        f.render_widget(gauge, header_layout[2]);
+    }
 
-    // Total Time
+    // Time worked today
     let spacer = Paragraph::new(time_text)
         .alignment(tui::layout::Alignment::Right)
         .style(Style::default()
             .fg(Color::Black)
-            .bg(Color::LightYellow)
+            .bg(theme.accent)
             .add_modifier(Modifier::BOLD));
-    f.render_widget(spacer, header_layout[2]);
+    f.render_widget(spacer, header_layout[3]);
+
+    if app.screen == Screen::History {
+        draw_history(f, app, &vertical_layout, config);
+        return;
+    }
 
     // Active Tasks List
+    let clock = app.clock.as_ref();
+    // tasks whose countdown is used up blink for a moment after one runs out
+    let flash = app.countdown_alert.is_some_and(|at| {
+        let since = clock.now().duration_since(at).unwrap_or_default();
+        since < COUNTDOWN_FLASH && since.as_millis() / 500 % 2 == 0
+    });
     let tasks: Vec<ListItem> = app
         .tasks
         .iter()
         .enumerate()
+        .filter(|(i, _)| app.is_listed(*i))
         .map(|(i, m)| {
-            let running_icon = if m.timer.is_running() {
+            // a folded task is running while any of its subtasks is
+            let is_running = m.timer.is_running() || (m.collapsed && app.subtask_running(i));
+            let running_icon = if is_running {
                 " ► "
             } else {
                 "  "
             };
-            let content = vec![Spans::from(Span::raw(format!(
-                "{}{} - {}",
-                running_icon,
-                m.description,
-                humantime::format_duration(Duration::new(m.timer.elapsed().as_secs(), 0))
-            )))];
+            // subtasks are indented under the task they're part of, which shows whether
+            // they're folded away
+            let fold = match (app.has_subtasks(i), m.collapsed) {
+                (true, true) => "▸ ",
+                (true, false) => "▾ ",
+                (false, _) => "",
+            };
+            let indent = "  ".repeat(app.depth(i));
+            let mut spans = vec![Span::raw(format!("{}{}{}{}", running_icon, indent, fold, m.description))];
+            spans.extend(badges(m.project.as_deref(), &m.tags));
+            // a split task shows the total of its parts
+            let (elapsed, estimate) = match app.has_subtasks(i) {
+                true => app.rollup(i),
+                false => (m.timer.elapsed(clock), m.estimate),
+            };
+            let countdown = app.countdown(i);
+            spans.push(match countdown {
+                Some(Countdown::Remaining(left)) => Span::raw(format!(
+                    " - {} left",
+                    humantime::format_duration(Duration::new(left.as_secs(), 0))
+                )),
+                Some(Countdown::Overtime(over)) if over.as_secs() == 0 => {
+                    Span::styled(" - time's up", Style::default().fg(theme.overrun))
+                }
+                Some(Countdown::Overtime(over)) => Span::styled(
+                    format!(" - -{} over", humantime::format_duration(Duration::new(over.as_secs(), 0))),
+                    Style::default().fg(theme.overrun),
+                ),
+                None => Span::raw(format!(
+                    " - {}",
+                    humantime::format_duration(Duration::new(elapsed.as_secs(), 0))
+                )),
+            });
+            if let Some(estimate) = estimate {
+                spans.push(Span::raw(format!(
+                    " / {} ",
+                    humantime::format_duration(Duration::new(estimate.as_secs(), 0))
+                )));
+                spans.push(Span::styled(
+                    estimate_gauge(elapsed, estimate, 20),
+                    match elapsed > estimate {
+                        true => Style::default().fg(theme.overrun),
+                        false => Style::default().fg(theme.on_track),
+                    },
+                ));
+            }
+            let content = vec![Spans::from(spans)];
+            if flash && matches!(countdown, Some(Countdown::Overtime(_))) {
+                return ListItem::new(content)
+                    .style(Style::default().bg(theme.overrun).fg(Color::Black));
+            }
+            let running = if m.is_break { theme.on_break } else { theme.running };
             ListItem::new(content).style(match app.selected_task == i {
-                true => match m.timer.is_running() {
-                    true => Style::default().bg(task_running_color).fg(Color::White),
+                true => match is_running {
+                    true => Style::default().bg(running).fg(Color::White),
                     false => Style::default().bg(Color::White).fg(Color::Black),
                 },
-                false => match m.timer.is_running() {
-                    true => Style::default().fg(task_running_color),
+                false => match is_running {
+                    true => Style::default().fg(running),
                     false => Style::default().fg(Color::White),
                 },
             })
@@ -213,32 +755,61 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     let tasks = List::new(tasks).block(
         Block::default()
             .borders(Borders::NONE)
-            .title(" Timers ")
+            .title(match app.filter.is_empty() {
+                true => " Timers ".to_string(),
+                false => format!(" Timers matching '{}' ", app.filter),
+            })
             .style(
-                match app.input_mode {
-                    InputMode::Normal => Style::default().fg(Color::White),
-                    InputMode::Editing => Style::default(),
+                match app.input_mode.is_text_entry() {
+                    true => Style::default(),
+                    false => Style::default().fg(Color::White),
                 }
                 .add_modifier(Modifier::BOLD),
             ),
     );
-    f.render_widget(tasks, vertical_layout[1]);
+    let mut list_area = vertical_layout[1];
+    if let Some(focused) = app.break_due() {
+        let banner = Paragraph::new(format!(
+            " You've been focused for {}. Press {} for a break, Esc to dismiss. ",
+            humantime::format_duration(match focused.as_secs() {
+                secs if secs < 60 => Duration::from_secs(secs),
+                secs => Duration::from_secs(secs / 60 * 60),
+            }),
+            config.keys.label(Action::Break)
+        ))
+        .style(Style::default().bg(theme.on_break).fg(Color::Black).add_modifier(Modifier::BOLD));
+        f.render_widget(banner, tui::layout::Rect { height: 1, ..list_area });
+        list_area.y += 1;
+        list_area.height = list_area.height.saturating_sub(1);
+    }
+    f.render_widget(tasks, list_area);
 
-    // Add Task input
-    let input = Paragraph::new(app.input.as_ref())
-        .style(match app.input_mode {
-            InputMode::Normal => Style::default(),
-            InputMode::Editing => Style::default(),
-        })
+    // Add Task / Adjust Time input
+    // the rest of a matching earlier description is suggested in grey after the cursor
+    let mut input_line = vec![Span::raw(app.input.text())];
+    if let Some(rest) = app.input.completion() {
+        input_line.push(Span::styled(rest, Style::default().fg(Color::DarkGray)));
+    }
+    if let InputMode::Searching = app.input_mode {
+        input_line = vec![Span::raw(app.filter.as_str())];
+    }
+    let input = Paragraph::new(Spans::from(input_line))
+        .style(Style::default())
         .block(
             Block::default().title(Span::styled(
-                "  Add Timer ",
                 match app.input_mode {
-                    InputMode::Editing => Style::default()
-                        .bg(Color::LightYellow)
+                    InputMode::Searching => "  Filter ",
+                    InputMode::Adjusting => "  Adjust Time ",
+                    InputMode::Renaming => "  Edit Timer ",
+                    InputMode::Splitting => "  Split Into Subtasks ",
+                    _ => "  Add Timer ",
+                },
+                match app.input_mode {
+                    InputMode::Normal => Style::default(),
+                    InputMode::Searching | InputMode::Editing | InputMode::Adjusting | InputMode::Renaming | InputMode::Splitting => Style::default()
+                        .bg(theme.accent)
                         .fg(Color::Black)
                         .add_modifier(Modifier::BOLD),
-                    InputMode::Normal => Style::default(),
                 },
             )),
         );
@@ -246,33 +817,75 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
 
     // Help Text
     let (msg, style) = match app.input_mode {
-        InputMode::Normal => (
+        InputMode::Searching => (
             vec![
-                Span::raw(""),
-                Span::styled("q", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(": quit, "),
-                Span::styled("a", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(": add, "),
-                Span::styled("j/k", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(": select, "),
-                Span::styled("l", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(": start/stop, "),
-                Span::styled("r", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(": reset, "),
-                Span::styled("x", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(": delete, "),
-                Span::styled("?", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(": help"),
+                Span::styled("+project", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(", "),
+                Span::styled("#tag", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" or text, "),
+                Span::styled("Enter", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to keep the filter, "),
+                Span::styled("Esc", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" to clear it"),
             ],
+            Style::default(),
+        ),
+        InputMode::Normal => (
+            help_bar(&config.keys, &[
+                (&[Action::Quit], "quit"),
+                (&[Action::Add], "add"),
+                (&[Action::Edit], "edit"),
+                (&[Action::Down, Action::Up], "select"),
+                (&[Action::Toggle], "start/stop"),
+                (&[Action::Reset], "reset"),
+                (&[Action::Delete], "delete"),
+                (&[Action::Adjust], "adjust"),
+                (&[Action::Filter], "filter"),
+                (&[Action::Undo, Action::Redo], "undo/redo"),
+                (&[Action::Report], "report"),
+                (&[Action::History], "history"),
+                (&[Action::Help], "help"),
+            ]),
             Style::default().add_modifier(Modifier::RAPID_BLINK),
         ),
-        InputMode::Editing => (
+        InputMode::Editing | InputMode::Renaming => (
             vec![
                 Span::raw("Press "),
                 Span::styled("Esc", Style::default().add_modifier(Modifier::BOLD)),
                 Span::raw(" to stop editing, "),
                 Span::styled("Enter", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(" to record the message"),
+                Span::raw(" to record the message ("),
+                Span::styled("~45m", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" sets an estimate, "),
+                Span::styled("+project #tag", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" file it), "),
+                Span::styled("Up/Down", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" recalls, "),
+                Span::styled("Tab", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" completes"),
+            ],
+            Style::default(),
+        ),
+        InputMode::Adjusting => (
+            vec![
+                Span::styled("+15m", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" or "),
+                Span::styled("-1h30m", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" adds or removes time, "),
+                Span::styled("=2h", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" sets the total, "),
+                Span::styled("Esc", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" cancels"),
+            ],
+            Style::default(),
+        ),
+        InputMode::Splitting => (
+            vec![
+                Span::raw("Subtasks separated by commas, e.g. "),
+                Span::styled("write tests ~30m, fix bug ~1h", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(", "),
+                Span::styled("Esc", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" cancels"),
             ],
             Style::default(),
         ),
@@ -281,6 +894,7 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     text.patch_style(style);
     let help_message = Paragraph::new(text);
     f.render_widget(help_message, vertical_layout[3]);
+    draw_status(f, app, theme, vertical_layout[3]);
 
 
     // cursor
@@ -288,11 +902,15 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         InputMode::Normal =>
             // Hide the cursor. `Frame` does this by default, so we don't need to do anything here
             {}
-        InputMode::Editing => {
+        InputMode::Searching => f.set_cursor(
+            vertical_layout[2].x + app.filter.width() as u16,
+            vertical_layout[2].y + 1,
+        ),
+        InputMode::Editing | InputMode::Adjusting | InputMode::Renaming | InputMode::Splitting => {
             // Make the cursor visible and ask tui-rs to put it at the specified coordinates after rendering
             f.set_cursor(
-                // Put cursor past the end of the input text
-                vertical_layout[2].x + app.input.width() as u16,
+                // Put cursor after the text in front of it
+                vertical_layout[2].x + app.input.cursor_width() as u16,
                 // Move one line down, from the border to the input line
                 vertical_layout[2].y + 1,
             )
@@ -300,8 +918,178 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     }
 }
 
+/// The history screen: completed quests grouped by the day they were completed on
+fn draw_history<B: Backend>(
+    f: &mut Frame<B>,
+    app: &App,
+    layout: &[tui::layout::Rect],
+    config: &Config,
+) {
+    let theme = &config.theme;
+    let quests = app.history.visible();
+
+    // one header row per day followed by that day's quests
+    let mut rows: Vec<ListItem> = Vec::new();
+    let mut selected_row = 0;
+    let mut i = 0;
+    while i < quests.len() {
+        let day = completed_day(quests[i]);
+        let count = quests[i..]
+            .iter()
+            .take_while(|quest| completed_day(quest) == day)
+            .count();
+        let total = quests[i..i + count]
+            .iter()
+            .fold(Duration::new(0, 0), |acc, quest| acc + quest.total_time);
+
+        rows.push(ListItem::new(Spans::from(vec![
+            Span::styled(
+                format!(" {} ", day.as_deref().unwrap_or("Unknown date")),
+                Style::default().fg(theme.accent).add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(
+                format!("{}", humantime::format_duration(Duration::new(total.as_secs(), 0))),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ])));
+
+        for (offset, quest) in quests[i..i + count].iter().enumerate() {
+            if i + offset == app.history.selected {
+                selected_row = rows.len();
+            }
+            let mut line = format!(
+                "   {} - {}",
+                quest.description,
+                humantime::format_duration(Duration::new(quest.total_time.as_secs(), 0))
+            );
+            if let Some(estimate) = quest.estimate {
+                line.push_str(&format!(
+                    " / {}",
+                    humantime::format_duration(Duration::new(estimate.as_secs(), 0))
+                ));
+            }
+            rows.push(ListItem::new(line));
+        }
+        i += count;
+    }
 
-fn draw_popup<B: Backend>(f: &mut Frame<B>) {
+    let mut state = ListState::default();
+    if !quests.is_empty() {
+        state.select(Some(selected_row));
+    }
+    let list = List::new(rows)
+        .block(Block::default()
+            .borders(Borders::NONE)
+            .title(format!(" Completed Quests ({}) ", quests.len()))
+            .style(Style::default().fg(Color::White).add_modifier(Modifier::BOLD)))
+        .highlight_style(Style::default().bg(Color::White).fg(Color::Black));
+    f.render_stateful_widget(list, layout[1], &mut state);
+
+    // Search input
+    let search = Paragraph::new(app.history.query.as_ref())
+        .block(
+            Block::default().title(Span::styled(
+                "  Search ",
+                match app.input_mode {
+                    InputMode::Searching => Style::default()
+                        .bg(theme.accent)
+                        .fg(Color::Black)
+                        .add_modifier(Modifier::BOLD),
+                    _ => Style::default(),
+                },
+            )),
+        );
+    f.render_widget(search, layout[2]);
+
+    // Help Text
+    let msg = match app.input_mode {
+        InputMode::Searching => vec![
+            Span::raw("Press "),
+            Span::styled("Enter", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(" to keep the filter, "),
+            Span::styled("Esc", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(" to clear it"),
+        ],
+        _ => help_bar(&config.keys, &[
+            (&[Action::Quit], "quit"),
+            (&[Action::History], "timers"),
+            (&[Action::Down, Action::Up], "select"),
+            (&[Action::Filter], "search"),
+            (&[Action::Reopen], "reopen"),
+        ]),
+    };
+    f.render_widget(Paragraph::new(Spans::from(msg)), layout[3]);
+    draw_status(f, app, &config.theme, layout[3]);
+
+    if let InputMode::Searching = app.input_mode {
+        f.set_cursor(
+            layout[2].x + app.history.query.width() as u16,
+            layout[2].y + 1,
+        )
+    }
+}
+
+/// Coloured `+project` and `#tag` labels to show after a description
+fn badges<'a>(project: Option<&'a str>, tags: &'a [String]) -> Vec<Span<'a>> {
+    let mut spans = Vec::new();
+    if let Some(project) = project {
+        spans.push(Span::raw(" "));
+        spans.push(Span::styled(
+            format!(" {} ", project),
+            Style::default().fg(Color::Black).bg(badge_color(project)),
+        ));
+    }
+    for tag in tags {
+        spans.push(Span::styled(
+            format!(" #{}", tag),
+            Style::default().fg(badge_color(tag)),
+        ));
+    }
+    spans
+}
+
+/// Colour for a project or tag, the same every time for the same name
+fn badge_color(name: &str) -> Color {
+    const COLORS: [Color; 6] = [
+        Color::Cyan,
+        Color::Green,
+        Color::Yellow,
+        Color::LightBlue,
+        Color::LightMagenta,
+        Color::LightRed,
+    ];
+    let hash = name
+        .bytes()
+        .fold(0usize, |acc, b| acc.wrapping_mul(31).wrapping_add(b as usize));
+    COLORS[hash % COLORS.len()]
+}
+
+/// Local date a quest was completed on, e.g. `Tue 01 Mar 2022`
+fn completed_day(quest: &CompletedTask) -> Option<String> {
+    quest
+        .completed_at
+        .map(|at| DateTime::<Local>::from(at).format("%a %d %b %Y").to_string())
+}
+
+/// Text progress bar of elapsed time against an estimate, `width` cells wide.
+/// Overrunning tasks show a full bar with the overrun percentage.
+fn estimate_gauge(elapsed: Duration, estimate: Duration, width: usize) -> String {
+    let ratio = if estimate.is_zero() {
+        1.0
+    } else {
+        elapsed.as_secs_f64() / estimate.as_secs_f64()
+    };
+    let filled = ((ratio.min(1.0) * width as f64).round() as usize).min(width);
+    format!(
+        "▕{}{}▏ {:.0}%",
+        "█".repeat(filled),
+        "░".repeat(width - filled),
+        ratio * 100.0
+    )
+}
+
+/// One line per pair of actions with the keys bound to them, from the active keymap
+fn draw_popup<B: Backend>(f: &mut Frame<B>, config: &Config) {
     let layout = Layout::default()
         .direction(Direction::Vertical)
         .margin(4)
@@ -309,21 +1097,27 @@ fn draw_popup<B: Backend>(f: &mut Frame<B>) {
                 Constraint::Min(1),
         ].as_ref())
         .split(f.size());
-    let backdrop = Block::default().style(Style::default().bg(Color::Rgb(20,20,20)));
+    let backdrop = Block::default().style(Style::default().bg(config.theme.header));
 
+    let entries: Vec<String> = Action::ALL
+        .iter()
+        .map(|action| {
+            let keys: Vec<String> = config
+                .keys
+                .keys(*action)
+                .iter()
+                .map(|key| key.to_string())
+                .collect();
+            format!(" {:<9} {}", format!("{}:", keys.join(",")), action.description())
+        })
+        .collect();
     let popup = List::new(
-            vec![
-                ListItem::new(
-                    Span::styled(" a:   add timer            j/k: select timer",
-                        Style::default().add_modifier(Modifier::BOLD))),
-                ListItem::new(
-                    Span::styled(" l:   start/stop timer     x: delete timer",
-                        Style::default().add_modifier(Modifier::BOLD))),
-                ListItem::new(
-                    Span::styled(" ?:   help                 q: quit",
-                        Style::default().add_modifier(Modifier::BOLD))),
-                
-            ]
+            entries
+                .chunks(2)
+                .map(|pair| ListItem::new(Span::styled(
+                    format!("{:<38}{}", pair[0], pair.get(1).map_or("", String::as_str)),
+                    Style::default().add_modifier(Modifier::BOLD))))
+                .collect::<Vec<ListItem>>()
         )
         .block(Block::default().title(Span::styled("Help:Keymaps",
             Style::default().add_modifier(Modifier::BOLD)
@@ -337,3 +1131,106 @@ fn draw_popup<B: Backend>(f: &mut Frame<B>) {
     f.render_widget(tui::widgets::Clear, layout[0]);
     f.render_widget(popup, layout[0]);
 }
+
+/// Cover the help bar with the last failure, if it's recent
+fn draw_status<B: Backend>(f: &mut Frame<B>, app: &App, theme: &Theme, area: tui::layout::Rect) {
+    if let Some(message) = app.status() {
+        let status = Paragraph::new(Span::styled(
+            format!(" {} ", message),
+            Style::default().bg(theme.overrun).fg(Color::Black).add_modifier(Modifier::BOLD),
+        ))
+        .style(Style::default().bg(theme.header));
+        f.render_widget(tui::widgets::Clear, area);
+        f.render_widget(status, area);
+    }
+}
+
+/// `key: label, ...` hints for the help bar, with the keys taken from the keymap. Actions
+/// sharing a label have their keys joined, e.g. `j/k: select`.
+fn help_bar(keys: &Keymap, entries: &[(&[Action], &'static str)]) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    for (i, (actions, label)) in entries.iter().enumerate() {
+        let bound: Vec<String> = actions.iter().map(|action| keys.label(*action)).collect();
+        spans.push(Span::styled(bound.join("/"), Style::default().add_modifier(Modifier::BOLD)));
+        spans.push(Span::raw(match i + 1 == entries.len() {
+            true => format!(": {}", label),
+            false => format!(": {}, ", label),
+        }));
+    }
+    spans
+}
+
+fn draw_report<B: Backend>(f: &mut Frame<B>, report: &str, theme: &Theme) {
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(4)
+        .constraints([
+                Constraint::Min(1),
+        ].as_ref())
+        .split(f.size());
+    let backdrop = Block::default().style(Style::default().bg(theme.header));
+
+    let popup = Paragraph::new(report)
+        .block(Block::default().title(Span::styled("Estimation Accuracy",
+            Style::default().add_modifier(Modifier::BOLD)
+        )).borders(Borders::ALL))
+        .style(Style::default()
+            .fg(Color::White)
+            .bg(Color::Rgb(0,0,0)));
+
+    f.render_widget(backdrop, f.size());
+    f.render_widget(tui::widgets::Clear, layout[0]);
+    f.render_widget(popup, layout[0]);
+}
+
+fn draw_prompt<B: Backend>(f: &mut Frame<B>, title: &str, text: &str) {
+    let height = text.lines().count() as u16 + 2;
+    let area = f.size();
+    let area = tui::layout::Rect {
+        x: area.width / 8,
+        y: area.height.saturating_sub(height) / 2,
+        width: area.width - area.width / 4,
+        height: height.min(area.height),
+    };
+
+    let popup = Paragraph::new(text)
+        .wrap(tui::widgets::Wrap { trim: false })
+        .block(Block::default().title(Span::styled(title,
+            Style::default().add_modifier(Modifier::BOLD)
+        )).borders(Borders::ALL))
+        .style(Style::default()
+            .fg(Color::White)
+            .bg(Color::Rgb(0,0,0)));
+
+    f.render_widget(tui::widgets::Clear, area);
+    f.render_widget(popup, area);
+}
+
+fn draw_picker<B: Backend>(f: &mut Frame<B>, title: &str, app: &App, selected: usize) {
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(4)
+        .constraints([
+                Constraint::Min(1),
+        ].as_ref())
+        .split(f.size());
+
+    let items: Vec<ListItem> = app
+        .tasks
+        .iter()
+        .map(|task| ListItem::new(format!(" {}", task.description)))
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(selected));
+    let popup = List::new(items)
+        .block(Block::default().title(Span::styled(title,
+            Style::default().add_modifier(Modifier::BOLD)
+        )).borders(Borders::ALL))
+        .style(Style::default()
+            .fg(Color::White)
+            .bg(Color::Rgb(0,0,0)))
+        .highlight_style(Style::default().bg(Color::White).fg(Color::Black));
+
+    f.render_widget(tui::widgets::Clear, layout[0]);
+    f.render_stateful_widget(popup, layout[0], &mut state);
+}
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+use crate::state::{CompletedTask, Task};
+
+/// How many actions can be undone
+const MAX_HISTORY: usize = 50;
+
+/// Undo/redo history of changes to the active tasks.
+///
+/// Every entry is a snapshot of the task list taken before (for undo) or after (for redo) an
+/// action, so restoring one simply swaps the task list back in.
+#[derive(Default, Serialize, Deserialize)]
+pub struct UndoStack {
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Name of the action, e.g. `delete`
+    pub action: String,
+    pub tasks: Vec<Task>,
+    pub selected_task: usize,
+    /// Completed quest written or removed by the action, which has to be put back in the
+    /// state it was in when the snapshot was taken
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quest: Option<QuestState>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct QuestState {
+    pub quest: CompletedTask,
+    /// Whether the quest was in `completed_quests` at the time of the snapshot
+    pub archived: bool,
+}
+
+impl Snapshot {
+    /// Snapshot of the current state to swap in for this one, so the swap can be reversed
+    pub fn counterpart(&self, tasks: &[Task], selected_task: usize) -> Snapshot {
+        Snapshot {
+            action: self.action.clone(),
+            tasks: tasks.to_vec(),
+            selected_task,
+            quest: self.quest.as_ref().map(|state| QuestState {
+                quest: state.quest.clone(),
+                archived: !state.archived,
+            }),
+        }
+    }
+}
+
+impl UndoStack {
+    /// Record the state before a new action. Anything that could be redone is forgotten.
+    pub fn push(&mut self, snapshot: Snapshot) {
+        self.undo.push(snapshot);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Attach the quest archived or reopened by the most recent action
+    pub fn set_last_quest(&mut self, quest: QuestState) {
+        if let Some(snapshot) = self.undo.last_mut() {
+            snapshot.quest = Some(quest);
+        }
+    }
+
+    /// Take the snapshot to go back to, storing `current` so it can be redone
+    pub fn undo(&mut self, current: impl FnOnce(&Snapshot) -> Snapshot) -> Option<Snapshot> {
+        let snapshot = self.undo.pop()?;
+        self.redo.push(current(&snapshot));
+        Some(snapshot)
+    }
+
+    /// Take the snapshot to go forward to, storing `current` so it can be undone again
+    pub fn redo(&mut self, current: impl FnOnce(&Snapshot) -> Snapshot) -> Option<Snapshot> {
+        let snapshot = self.redo.pop()?;
+        self.undo.push(current(&snapshot));
+        Some(snapshot)
+    }
+}